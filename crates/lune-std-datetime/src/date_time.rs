@@ -1,4 +1,4 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, time::SystemTime};
 
 use mlua::prelude::*;
 
@@ -202,6 +202,12 @@ impl DateTime {
     }
 }
 
+impl From<DateTime> for SystemTime {
+    fn from(value: DateTime) -> Self {
+        Self::from(value.inner)
+    }
+}
+
 impl LuaUserData for DateTime {
     fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("unixTimestamp", |_, this| Ok(this.inner.timestamp()));
@@ -0,0 +1,81 @@
+use std::{path::PathBuf, time::SystemTime};
+
+use filetime::FileTime;
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use tokio::fs;
+
+use super::options::{FsPermissionsOptions, FsTimesOptions};
+
+pub async fn set_permissions(path: PathBuf, options: FsPermissionsOptions) -> LuaResult<()> {
+    let mut permissions = fs::metadata(&path).await?.permissions();
+
+    if let Some(mode) = options.mode {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(mode);
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = mode;
+            return Err(LuaError::runtime(
+                "Setting permission mode bits is only supported on Unix",
+            ));
+        }
+    }
+
+    // NOTE: On Unix, this only sets or clears the write bit for the
+    // owner, since setting write bits for everyone would make the path
+    // world-writable, and it must be applied *after* any given mode
+    if let Some(read_only) = options.read_only {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = permissions.mode();
+            permissions.set_mode(if read_only {
+                mode & !0o200
+            } else {
+                mode | 0o200
+            });
+        }
+        #[cfg(not(unix))]
+        {
+            permissions.set_readonly(read_only);
+        }
+    }
+
+    fs::set_permissions(&path, permissions).await?;
+    Ok(())
+}
+
+pub async fn chown(lua: &Lua, path: PathBuf, uid: Option<u32>, gid: Option<u32>) -> LuaResult<()> {
+    #[cfg(unix)]
+    {
+        lua.spawn_blocking(move || std::os::unix::fs::chown(path, uid, gid))
+            .await
+            .into_lua_err()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (lua, path, uid, gid);
+        Err(LuaError::runtime(
+            "Changing file ownership is only supported on Unix",
+        ))
+    }
+}
+
+pub async fn set_times(lua: &Lua, path: PathBuf, options: FsTimesOptions) -> LuaResult<()> {
+    let to_file_time = |time| FileTime::from_system_time(SystemTime::from(time));
+    let accessed = options.accessed.map(to_file_time);
+    let modified = options.modified.map(to_file_time);
+
+    lua.spawn_blocking(move || match (accessed, modified) {
+        (Some(accessed), Some(modified)) => filetime::set_file_times(path, accessed, modified),
+        (Some(accessed), None) => filetime::set_file_atime(path, accessed),
+        (None, Some(modified)) => filetime::set_file_mtime(path, modified),
+        (None, None) => Ok(()),
+    })
+    .await
+    .into_lua_err()
+}
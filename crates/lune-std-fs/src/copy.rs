@@ -3,19 +3,134 @@ use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
 use mlua::prelude::*;
-use tokio::fs;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
 
-use super::options::FsWriteOptions;
+use super::{link::create_symlink, options::FsWriteOptions};
+
+// NOTE: Files are copied in chunks of this size when progress is
+// requested, which is large enough to not slow down copying much,
+// but small enough to report progress often for large files
+const PROGRESS_CHUNK_SIZE: usize = 1024 * 1024;
 
 pub struct CopyContents {
     // Vec<(relative depth, path)>
     pub dirs: Vec<(usize, PathBuf)>,
     pub files: Vec<(usize, PathBuf)>,
+    pub symlinks: Vec<(usize, PathBuf)>,
+    // Total size of all files, in bytes
+    pub bytes_total: u64,
+}
+
+/**
+    What was copied by a call to [`copy`].
+*/
+pub enum Copied {
+    // A single file or symlink
+    Path,
+    // A directory, along with all of its contents that passed the filter
+    Dir(CopyContents),
+    // Nothing, since the source path did not pass the filter
+    Nothing,
+}
+
+/**
+    Calls the filter callback in the given options, if any, to check
+    if the given path should be copied - paths are always included
+    if there is no filter.
+
+    Note that the filter is called synchronously, and must not yield.
+*/
+fn filter_path(path: &Path, options: &FsWriteOptions<'_>) -> LuaResult<bool> {
+    match &options.filter {
+        Some(filter) => filter.call(path.to_string_lossy().to_string()),
+        None => Ok(true),
+    }
+}
+
+/**
+    Checks if the path at `root` joined with `relative` should be copied.
+
+    See [`filter_path`] for more information.
+*/
+fn is_included(root: &Path, relative: &Path, options: &FsWriteOptions<'_>) -> LuaResult<bool> {
+    filter_path(&root.join(relative), options)
 }
 
-async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyContents> {
+/**
+    Keeps track of and reports progress for a copy operation,
+    using the progress callback in the given options, if any.
+
+    Note that the progress callback is called synchronously, and must not yield.
+*/
+struct CopyProgress<'a, 'lua> {
+    callback: Option<&'a LuaFunction<'lua>>,
+    bytes_done: u64,
+    bytes_total: u64,
+}
+
+impl<'a, 'lua> CopyProgress<'a, 'lua> {
+    fn new(options: &'a FsWriteOptions<'lua>, bytes_total: u64) -> Self {
+        Self {
+            callback: options.on_progress.as_ref(),
+            bytes_done: 0,
+            bytes_total,
+        }
+    }
+
+    fn advance(&mut self, bytes: u64, path: &Path) -> LuaResult<()> {
+        self.bytes_done += bytes;
+        match self.callback {
+            Some(callback) => callback.call((
+                self.bytes_done,
+                self.bytes_total,
+                path.to_string_lossy().to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /**
+        Copies a single file from `source` to `target`, reporting progress as it goes.
+
+        Without a progress callback this uses the fastest available method for
+        copying files, otherwise the file is copied in chunks to report progress.
+    */
+    async fn copy_file(&mut self, source: &Path, target: &Path) -> LuaResult<()> {
+        if self.callback.is_none() {
+            let bytes = fs::copy(source, target).await?;
+            self.bytes_done += bytes;
+            return Ok(());
+        }
+
+        let mut source_file = fs::File::open(source).await?;
+        let mut target_file = fs::File::create(target).await?;
+        let mut buffer = vec![0; PROGRESS_CHUNK_SIZE];
+        loop {
+            let bytes = source_file.read(&mut buffer).await?;
+            if bytes == 0 {
+                break;
+            }
+            target_file.write_all(&buffer[..bytes]).await?;
+            self.advance(bytes as u64, source)?;
+        }
+        target_file.flush().await?;
+
+        // Copy permissions the same way that fs::copy would
+        let permissions = source_file.metadata().await?.permissions();
+        fs::set_permissions(target, permissions).await?;
+
+        Ok(())
+    }
+}
+
+async fn get_contents_at(root: PathBuf, options: &FsWriteOptions<'_>) -> LuaResult<CopyContents> {
     let mut dirs = Vec::new();
     let mut files = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut bytes_total = 0;
 
     let mut queue = VecDeque::new();
 
@@ -33,12 +148,20 @@ async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyCont
     // when we find any new descendant directories
     // FUTURE: Try to do async reading here concurrently to speed it up a bit
     while let Some((current_depth, current_path)) = queue.pop_front() {
-        let meta = fs::metadata(&current_path).await?;
+        // Filtered out directories are skipped along with all of their descendants
+        // SAFETY: Since we only ever push paths relative to the root, unwrap is safe
+        let relative = current_path.strip_prefix(&normalized_root).unwrap();
+        if !is_included(&root, relative, options)? {
+            continue;
+        }
+
+        let meta = if options.preserve_symlinks {
+            fs::symlink_metadata(&current_path).await?
+        } else {
+            fs::metadata(&current_path).await?
+        };
         if meta.is_symlink() {
-            return Err(LuaError::RuntimeError(format!(
-                "Symlinks are not yet supported, encountered at path '{}'",
-                current_path.display()
-            )));
+            symlinks.push((current_depth, current_path));
         } else if meta.is_dir() {
             // FUTURE: Add an option in FsWriteOptions for max depth and limit it here
             let mut entries = fs::read_dir(&current_path).await?;
@@ -47,6 +170,7 @@ async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyCont
             }
             dirs.push((current_depth, current_path));
         } else {
+            bytes_total += meta.len();
             files.push((current_depth, current_path));
         }
     }
@@ -59,6 +183,12 @@ async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyCont
     for (_, file) in &mut files {
         *file = file.strip_prefix(&normalized_root).unwrap().to_path_buf();
     }
+    for (_, symlink) in &mut symlinks {
+        *symlink = symlink
+            .strip_prefix(&normalized_root)
+            .unwrap()
+            .to_path_buf();
+    }
 
     // FUTURE: Deduplicate paths such that these directories:
     // - foo/
@@ -66,7 +196,12 @@ async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyCont
     // - foo/bar/baz/
     // turn into a single foo/bar/baz/ and let create_dir_all do the heavy lifting
 
-    Ok(CopyContents { dirs, files })
+    Ok(CopyContents {
+        dirs,
+        files,
+        symlinks,
+        bytes_total,
+    })
 }
 
 async fn ensure_no_dir_exists(path: impl AsRef<Path>) -> LuaResult<()> {
@@ -91,17 +226,37 @@ async fn ensure_no_file_exists(path: impl AsRef<Path>) -> LuaResult<()> {
     }
 }
 
+/**
+    Copies a file or directory recursively from `source` to `target`.
+
+    Returns the contents that were copied if `source` was a directory, which
+    is useful for removing exactly what was copied when moving filtered paths.
+*/
 pub async fn copy(
     source: impl AsRef<Path>,
     target: impl AsRef<Path>,
-    options: FsWriteOptions,
-) -> LuaResult<()> {
+    options: &FsWriteOptions<'_>,
+) -> LuaResult<Copied> {
     let source = source.as_ref();
     let target = target.as_ref();
 
+    if options.preserve_symlinks {
+        match fs::symlink_metadata(&source).await {
+            Ok(meta) if meta.is_symlink() => {
+                if !filter_path(source, options)? {
+                    return Ok(Copied::Nothing);
+                }
+                copy_symlink(source, target, options).await?;
+                return Ok(Copied::Path);
+            }
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+
     // Check if we got a file or directory - we will handle them differently below
-    let (is_dir, is_file) = match fs::metadata(&source).await {
-        Ok(meta) => (meta.is_dir(), meta.is_file()),
+    let (is_dir, is_file, len) = match fs::metadata(&source).await {
+        Ok(meta) => (meta.is_dir(), meta.is_file(), meta.len()),
         Err(e) if e.kind() == ErrorKind::NotFound => {
             return Err(LuaError::RuntimeError(format!(
                 "No file or directory exists at the path '{}'",
@@ -117,6 +272,12 @@ pub async fn copy(
         )));
     }
 
+    // Single files are filtered the same as files found in directories, but the
+    // root of a directory is not, since the filter is used for its contents
+    if is_file && !filter_path(source, options)? {
+        return Ok(Copied::Nothing);
+    }
+
     // Perform copying:
     //
     // 1. If we are not allowed to overwrite, make sure nothing exists at the target path
@@ -133,8 +294,10 @@ pub async fn copy(
     }
 
     if is_file {
-        fs::copy(source, target).await?;
-    } else if is_dir {
+        let mut progress = CopyProgress::new(options, len);
+        progress.copy_file(source, target).await?;
+        Ok(Copied::Path)
+    } else {
         let contents = get_contents_at(source.to_path_buf(), options).await?;
 
         if options.overwrite {
@@ -157,8 +320,100 @@ pub async fn copy(
         for (_, dir) in &contents.dirs {
             fs::create_dir_all(target.join(dir)).await?;
         }
+        let mut progress = CopyProgress::new(options, contents.bytes_total);
         for (_, file) in &contents.files {
-            fs::copy(source.join(file), target.join(file)).await?;
+            progress
+                .copy_file(&source.join(file), &target.join(file))
+                .await?;
+        }
+        for (_, symlink) in &contents.symlinks {
+            let link_target = fs::read_link(source.join(symlink)).await?;
+            create_symlink(link_target, target.join(symlink)).await?;
+        }
+
+        Ok(Copied::Dir(contents))
+    }
+}
+
+/**
+    Copies the symlink at `source` itself to `target`, without following it.
+
+    Note that the link target is copied as-is, meaning relative
+    link targets will be relative to the new link location.
+*/
+async fn copy_symlink(source: &Path, target: &Path, options: &FsWriteOptions<'_>) -> LuaResult<()> {
+    match fs::symlink_metadata(&target).await {
+        Ok(_) if !options.overwrite => {
+            return Err(LuaError::RuntimeError(format!(
+                "A file or directory already exists at the path '{}'",
+                target.display()
+            )))
+        }
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(target).await?,
+        Ok(_) => fs::remove_file(target).await?,
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let link_target = fs::read_link(source).await?;
+    create_symlink(link_target, target).await?;
+
+    Ok(())
+}
+
+fn is_cross_device_error(e: &std::io::Error) -> bool {
+    e.kind() == ErrorKind::CrossesDevices
+}
+
+/**
+    Moves a file or directory from `source` to `target`.
+
+    Moving is done by renaming whenever possible, falling back to copying
+    and then removing the source if the paths are on different devices.
+
+    If a filter is given, only the paths that pass the filter are moved, and any
+    paths that do not pass it are left in place, meaning renaming is not possible.
+*/
+pub async fn move_path(
+    source: &Path,
+    target: &Path,
+    options: &FsWriteOptions<'_>,
+) -> LuaResult<()> {
+    if options.filter.is_none() {
+        match fs::rename(source, target).await {
+            Ok(()) => {
+                // Nothing was copied, but we should still let
+                // the caller know that the move was completed
+                if let Some(callback) = &options.on_progress {
+                    let len = match fs::symlink_metadata(target).await {
+                        Ok(meta) if meta.is_file() => meta.len(),
+                        _ => 0,
+                    };
+                    callback.call::<_, ()>((len, len, source.to_string_lossy().to_string()))?;
+                }
+                return Ok(());
+            }
+            Err(e) if is_cross_device_error(&e) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    match copy(source, target, options).await? {
+        Copied::Nothing => {}
+        Copied::Path => fs::remove_file(source).await?,
+        Copied::Dir(_) if options.filter.is_none() => fs::remove_dir_all(source).await?,
+        Copied::Dir(contents) => {
+            for (_, file) in contents.files.iter().chain(&contents.symlinks) {
+                fs::remove_file(source.join(file)).await?;
+            }
+            // Directories that still contain filtered out paths can not be
+            // removed, and are left in place, so errors here are expected
+            let mut dirs = contents.dirs;
+            dirs.sort_by(|(a, _), (b, _)| b.cmp(a));
+            for (_, dir) in dirs {
+                fs::remove_dir(source.join(dir)).await.ok();
+            }
+            fs::remove_dir(source).await.ok();
         }
     }
 
@@ -0,0 +1,86 @@
+use std::{
+    ffi::OsStr,
+    io::ErrorKind as IoErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bstr::ByteVec;
+use mlua::prelude::*;
+use tokio::{fs, sync::OnceCell};
+
+use super::metadata::{FsMetadata, FsMetadataKind};
+
+/**
+    A single entry found when reading or walking a directory.
+
+    Metadata for the entry is only fetched once it is requested,
+    and is then cached for the lifetime of the entry.
+*/
+#[derive(Debug, Clone)]
+pub struct FsDirEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) kind: FsMetadataKind,
+    pub(crate) depth: usize,
+    pub(crate) metadata: Arc<OnceCell<FsMetadata>>,
+}
+
+impl FsDirEntry {
+    pub fn new(path: PathBuf, kind: FsMetadataKind, depth: usize) -> Self {
+        Self {
+            path,
+            kind,
+            depth,
+            metadata: Arc::new(OnceCell::new()),
+        }
+    }
+
+    pub async fn metadata(&self) -> LuaResult<FsMetadata> {
+        let metadata = self
+            .metadata
+            .get_or_try_init(|| async {
+                // NOTE: Entries describe the link itself and not what it
+                // points to, so we must not follow symlinks here either
+                match fs::symlink_metadata(&self.path).await {
+                    Err(e) if e.kind() == IoErrorKind::NotFound => Ok(FsMetadata::not_found()),
+                    Ok(meta) => Ok(FsMetadata::from(meta)),
+                    Err(e) => Err(LuaError::from(e)),
+                }
+            })
+            .await?;
+        Ok(metadata.clone())
+    }
+}
+
+/**
+    Converts an OS string into a Lua string, without
+    failing or losing any information for non-UTF-8 names.
+*/
+fn os_str_to_lua<'lua>(lua: &'lua Lua, s: &OsStr) -> LuaResult<LuaString<'lua>> {
+    lua.create_string(Vec::from_os_str_lossy(s))
+}
+
+pub(crate) fn path_to_lua<'lua>(lua: &'lua Lua, path: &Path) -> LuaResult<LuaString<'lua>> {
+    os_str_to_lua(lua, path.as_os_str())
+}
+
+impl LuaUserData for FsDirEntry {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "DirEntry");
+        fields.add_field_method_get("name", |lua, this| {
+            os_str_to_lua(lua, this.path.file_name().unwrap_or_default())
+        });
+        fields.add_field_method_get("path", |lua, this| path_to_lua(lua, &this.path));
+        fields.add_field_method_get("kind", |_, this| Ok(this.kind));
+        fields.add_field_method_get("depth", |_, this| Ok(this.depth));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("metadata", |_, this, (): ()| async move {
+            this.metadata().await
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |lua, this, ()| {
+            path_to_lua(lua, &this.path)
+        });
+    }
+}
@@ -0,0 +1,168 @@
+#![allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
+
+use std::{io::SeekFrom, sync::Arc};
+
+use bstr::{BString, ByteSlice};
+use mlua::prelude::*;
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
+    sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard},
+};
+
+/**
+    A handle to an open file that can be used from Lua.
+
+    Reads are buffered so that reading line-by-line is cheap, and
+    the handle is closed automatically when garbage collected.
+*/
+#[derive(Debug, Clone)]
+pub struct FsFile {
+    inner: Arc<AsyncMutex<Option<BufReader<File>>>>,
+}
+
+impl FsFile {
+    pub fn new(file: File) -> Self {
+        Self {
+            inner: Arc::new(AsyncMutex::new(Some(BufReader::new(file)))),
+        }
+    }
+
+    async fn lock(&self) -> LuaResult<AsyncMutexGuard<'_, Option<BufReader<File>>>> {
+        let guard = self.inner.lock().await;
+        if guard.is_none() {
+            return Err(LuaError::runtime("File has already been closed"));
+        }
+        Ok(guard)
+    }
+
+    pub async fn read(&self, amount: Option<usize>) -> LuaResult<Option<Vec<u8>>> {
+        let mut guard = self.lock().await?;
+        let file = guard.as_mut().unwrap();
+
+        let mut bytes = Vec::new();
+        match amount {
+            Some(amount) => {
+                file.take(amount as u64).read_to_end(&mut bytes).await?;
+            }
+            None => {
+                file.read_to_end(&mut bytes).await?;
+            }
+        }
+
+        Ok(if bytes.is_empty() { None } else { Some(bytes) })
+    }
+
+    pub async fn read_line(&self) -> LuaResult<Option<Vec<u8>>> {
+        let mut guard = self.lock().await?;
+        let file = guard.as_mut().unwrap();
+
+        let mut bytes = Vec::new();
+        if file.read_until(b'\n', &mut bytes).await? == 0 {
+            return Ok(None);
+        }
+
+        // Strip the trailing newline, including a preceding
+        // carriage return to also handle CRLF line endings
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+        }
+
+        Ok(Some(bytes))
+    }
+
+    pub async fn write(&self, contents: impl AsRef<[u8]>) -> LuaResult<()> {
+        let mut guard = self.lock().await?;
+        let file = guard.as_mut().unwrap();
+
+        // Writes go directly to the underlying file, which is positioned after
+        // any data that has been read into the buffer but not yet consumed, so
+        // we seek to our logical position first, which also discards the buffer
+        if !file.buffer().is_empty() {
+            file.seek(SeekFrom::Current(0)).await?;
+        }
+
+        file.write_all(contents.as_ref()).await?;
+        Ok(())
+    }
+
+    pub async fn seek(&self, from: SeekFrom) -> LuaResult<u64> {
+        let mut guard = self.lock().await?;
+        let file = guard.as_mut().unwrap();
+
+        Ok(file.seek(from).await?)
+    }
+
+    pub async fn flush(&self) -> LuaResult<()> {
+        let mut guard = self.lock().await?;
+        let file = guard.as_mut().unwrap();
+
+        file.flush().await?;
+        Ok(())
+    }
+
+    pub async fn close(&self) -> LuaResult<()> {
+        let mut guard = self.lock().await?;
+        let mut file = guard.take().unwrap();
+
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+fn parse_seek_from(whence: Option<String>, offset: Option<i64>) -> LuaResult<SeekFrom> {
+    let offset = offset.unwrap_or_default();
+    match whence.as_deref().map(str::trim) {
+        None | Some("set") => match u64::try_from(offset) {
+            Ok(offset) => Ok(SeekFrom::Start(offset)),
+            Err(_) => Err(LuaError::RuntimeError(format!(
+                "Seek offset must be positive when seeking from the start of a file, got {offset}"
+            ))),
+        },
+        Some("current") => Ok(SeekFrom::Current(offset)),
+        Some("end") => Ok(SeekFrom::End(offset)),
+        Some(whence) => Err(LuaError::RuntimeError(format!(
+            "Invalid seek whence - got '{whence}', expected one of 'set', 'current', 'end'"
+        ))),
+    }
+}
+
+impl LuaUserData for FsFile {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("read", |lua, this, amount: Option<usize>| async move {
+            match this.read(amount).await? {
+                Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+
+        methods.add_async_method("readLine", |lua, this, (): ()| async move {
+            match this.read_line().await? {
+                Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+
+        methods.add_async_method("write", |_, this, contents: BString| async move {
+            this.write(contents.as_bytes()).await
+        });
+
+        methods.add_async_method(
+            "seek",
+            |_, this, (whence, offset): (Option<String>, Option<i64>)| async move {
+                this.seek(parse_seek_from(whence, offset)?).await
+            },
+        );
+
+        methods.add_async_method("flush", |_, this, (): ()| async move { this.flush().await });
+
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "File");
+    }
+}
@@ -9,13 +9,34 @@ use tokio::fs;
 
 use lune_utils::TableBuilder;
 
+mod attributes;
 mod copy;
+mod entry;
+mod file;
+mod link;
+mod lock;
 mod metadata;
 mod options;
+mod temp;
+mod walk;
+mod watch;
+mod write;
 
-use self::copy::copy;
-use self::metadata::FsMetadata;
-use self::options::FsWriteOptions;
+use self::attributes::{chown, set_permissions, set_times};
+use self::copy::{copy, move_path};
+use self::entry::{path_to_lua, FsDirEntry};
+pub use self::file::FsFile;
+use self::link::create_symlink;
+use self::lock::{lock, FsLock};
+use self::metadata::{FsMetadata, FsMetadataKind};
+use self::options::{
+    FsLockOptions, FsMetadataOptions, FsOpenOptions, FsPermissionsOptions, FsTimesOptions,
+    FsWalkOptions, FsWatchOptions, FsWriteOptions,
+};
+use self::temp::{temp_dir, temp_file, FsTemp};
+use self::walk::{glob, walk_dir, FsDirWalker};
+use self::watch::watch;
+use self::write::write_file;
 
 /**
     Creates the `fs` standard library module.
@@ -28,6 +49,7 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
         .with_async_function("readFile", fs_read_file)?
         .with_async_function("readDir", fs_read_dir)?
+        .with_async_function("readDirEntries", fs_read_dir_entries)?
         .with_async_function("writeFile", fs_write_file)?
         .with_async_function("writeDir", fs_write_dir)?
         .with_async_function("removeFile", fs_remove_file)?
@@ -37,9 +59,34 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
         .with_async_function("isDir", fs_is_dir)?
         .with_async_function("move", fs_move)?
         .with_async_function("copy", fs_copy)?
+        .with_async_function("symlink", fs_symlink)?
+        .with_async_function("hardlink", fs_hardlink)?
+        .with_async_function("readLink", fs_read_link)?
+        .with_async_function("canonicalize", fs_canonicalize)?
+        .with_async_function("setPermissions", fs_set_permissions)?
+        .with_async_function("setReadOnly", fs_set_read_only)?
+        .with_async_function("chown", fs_chown)?
+        .with_async_function("setTimes", fs_set_times)?
+        .with_async_function("open", fs_open)?
+        .with_async_function("lock", fs_lock)?
+        .with_function("tempDir", fs_temp_dir)?
+        .with_function("tempFile", fs_temp_file)?
+        .with_function("watch", fs_watch)?
+        .with_function("walkDir", fs_walk_dir)?
+        .with_function("glob", fs_glob)?
         .build_readonly()
 }
 
+/**
+    Cleans up any resources created by the `fs` standard library module.
+
+    This removes any temporary files and directories that
+    were created but not yet removed by the Lua state.
+*/
+pub fn cleanup(lua: &Lua) {
+    temp::cleanup(lua);
+}
+
 async fn fs_read_file(lua: &Lua, path: String) -> LuaResult<LuaString> {
     let bytes = fs::read(&path).await.into_lua_err()?;
 
@@ -62,8 +109,25 @@ async fn fs_read_dir(_: &Lua, path: String) -> LuaResult<Vec<String>> {
     Ok(dir_strings)
 }
 
-async fn fs_write_file(_: &Lua, (path, contents): (String, BString)) -> LuaResult<()> {
-    fs::write(&path, contents.as_bytes()).await.into_lua_err()
+async fn fs_read_dir_entries(_: &Lua, path: String) -> LuaResult<Vec<FsDirEntry>> {
+    let mut entries = Vec::new();
+    let mut dir = fs::read_dir(&path).await.into_lua_err()?;
+    while let Some(dir_entry) = dir.next_entry().await.into_lua_err()? {
+        let kind = match dir_entry.file_type().await {
+            Ok(file_type) => FsMetadataKind::from(file_type),
+            Err(e) if e.kind() == IoErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        entries.push(FsDirEntry::new(dir_entry.path(), kind, 1));
+    }
+    Ok(entries)
+}
+
+async fn fs_write_file(
+    _: &Lua,
+    (path, contents, options): (String, BString, FsWriteOptions<'_>),
+) -> LuaResult<()> {
+    write_file(path.as_ref(), contents.as_bytes(), options).await
 }
 
 async fn fs_write_dir(_: &Lua, path: String) -> LuaResult<()> {
@@ -78,8 +142,16 @@ async fn fs_remove_dir(_: &Lua, path: String) -> LuaResult<()> {
     fs::remove_dir_all(&path).await.into_lua_err()
 }
 
-async fn fs_metadata(_: &Lua, path: String) -> LuaResult<FsMetadata> {
-    match fs::metadata(path).await {
+async fn fs_metadata(
+    _: &Lua,
+    (path, options): (String, FsMetadataOptions),
+) -> LuaResult<FsMetadata> {
+    let result = if options.follow_symlinks {
+        fs::metadata(path).await
+    } else {
+        fs::symlink_metadata(path).await
+    };
+    match result {
         Err(e) if e.kind() == IoErrorKind::NotFound => Ok(FsMetadata::not_found()),
         Ok(meta) => Ok(FsMetadata::from(meta)),
         Err(e) => Err(e.into()),
@@ -102,7 +174,10 @@ async fn fs_is_dir(_: &Lua, path: String) -> LuaResult<bool> {
     }
 }
 
-async fn fs_move(_: &Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
+async fn fs_move(
+    _: &Lua,
+    (from, to, options): (String, String, FsWriteOptions<'_>),
+) -> LuaResult<()> {
     let path_from = PathBuf::from(from);
     if !path_from.exists() {
         return Err(LuaError::RuntimeError(format!(
@@ -117,10 +192,98 @@ async fn fs_move(_: &Lua, (from, to, options): (String, String, FsWriteOptions))
             path_to.display()
         )));
     }
-    fs::rename(path_from, path_to).await.into_lua_err()?;
+    move_path(&path_from, &path_to, &options).await
+}
+
+async fn fs_copy(
+    _: &Lua,
+    (from, to, options): (String, String, FsWriteOptions<'_>),
+) -> LuaResult<()> {
+    copy(from, to, &options).await?;
     Ok(())
 }
 
-async fn fs_copy(_: &Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
-    copy(from, to, options).await
+async fn fs_symlink(_: &Lua, (target, link): (String, String)) -> LuaResult<()> {
+    create_symlink(target, link).await.into_lua_err()
+}
+
+async fn fs_hardlink(_: &Lua, (source, link): (String, String)) -> LuaResult<()> {
+    fs::hard_link(source, link).await.into_lua_err()
+}
+
+async fn fs_read_link(lua: &Lua, path: String) -> LuaResult<LuaString> {
+    let target = fs::read_link(path).await.into_lua_err()?;
+    path_to_lua(lua, &target)
+}
+
+async fn fs_canonicalize(lua: &Lua, path: String) -> LuaResult<LuaString> {
+    let canonical = fs::canonicalize(path).await.into_lua_err()?;
+    path_to_lua(lua, &canonical)
+}
+
+async fn fs_set_permissions(
+    _: &Lua,
+    (path, options): (String, FsPermissionsOptions),
+) -> LuaResult<()> {
+    set_permissions(PathBuf::from(path), options).await
+}
+
+async fn fs_set_read_only(_: &Lua, (path, read_only): (String, bool)) -> LuaResult<()> {
+    let options = FsPermissionsOptions {
+        read_only: Some(read_only),
+        ..Default::default()
+    };
+    set_permissions(PathBuf::from(path), options).await
+}
+
+async fn fs_chown(
+    lua: &Lua,
+    (path, uid, gid): (String, Option<u32>, Option<u32>),
+) -> LuaResult<()> {
+    chown(lua, PathBuf::from(path), uid, gid).await
+}
+
+async fn fs_set_times(lua: &Lua, (path, options): (String, FsTimesOptions)) -> LuaResult<()> {
+    set_times(lua, PathBuf::from(path), options).await
+}
+
+async fn fs_open(_: &Lua, (path, options): (String, FsOpenOptions)) -> LuaResult<FsFile> {
+    let file = options.to_open_options().open(&path).await.into_lua_err()?;
+    Ok(FsFile::new(file))
+}
+
+async fn fs_lock(lua: &Lua, (path, options): (String, FsLockOptions)) -> LuaResult<Option<FsLock>> {
+    lock(lua, PathBuf::from(path), options).await
+}
+
+fn fs_temp_dir(lua: &Lua, prefix: Option<String>) -> LuaResult<FsTemp> {
+    temp_dir(lua, prefix)
+}
+
+fn fs_temp_file(
+    lua: &Lua,
+    (prefix, suffix): (Option<String>, Option<String>),
+) -> LuaResult<FsTemp> {
+    temp_file(lua, prefix, suffix)
+}
+
+fn fs_watch<'lua>(
+    lua: &'lua Lua,
+    (path, options, callback): (String, LuaValue<'lua>, Option<LuaFunction<'lua>>),
+) -> LuaResult<LuaTable<'lua>> {
+    // The options argument is optional, so the callback may be given in its place
+    let (options, callback) = match (options, callback) {
+        (LuaValue::Function(callback), None) => (FsWatchOptions::default(), callback),
+        (options, Some(callback)) => (FsWatchOptions::from_lua(options, lua)?, callback),
+        (_, None) => return Err(LuaError::runtime("Missing callback for watching path")),
+    };
+    watch(lua, path, options, callback)
+}
+
+fn fs_walk_dir(lua: &Lua, (path, options): (String, FsWalkOptions)) -> LuaResult<FsDirWalker> {
+    walk_dir(lua, PathBuf::from(path), options)
+}
+
+fn fs_glob(lua: &Lua, (pattern, options): (String, FsWalkOptions)) -> LuaResult<FsDirWalker> {
+    glob(lua, pattern, options)
 }
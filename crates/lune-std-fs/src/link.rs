@@ -0,0 +1,34 @@
+use std::{io::Result as IoResult, path::Path};
+
+use tokio::fs;
+
+/**
+    Creates a symlink at `link` pointing to `target`.
+
+    On Windows, symlinks to files and directories are different
+    kinds of links, so the target must exist for us to know
+    which one to create - relative targets are resolved
+    relative to the directory containing the link.
+*/
+pub async fn create_symlink(target: impl AsRef<Path>, link: impl AsRef<Path>) -> IoResult<()> {
+    let target = target.as_ref();
+    let link = link.as_ref();
+
+    #[cfg(unix)]
+    {
+        fs::symlink(target, link).await
+    }
+
+    #[cfg(windows)]
+    {
+        let resolved = match link.parent() {
+            Some(parent) if target.is_relative() => parent.join(target),
+            _ => target.to_path_buf(),
+        };
+        if fs::metadata(&resolved).await?.is_dir() {
+            fs::symlink_dir(target, link).await
+        } else {
+            fs::symlink_file(target, link).await
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind as IoErrorKind, Result as IoResult},
+    path::PathBuf,
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use fd_lock::RwLock;
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use tokio::sync::oneshot;
+
+use super::options::FsLockOptions;
+
+type ReleaseChannels = (mpsc::Sender<()>, oneshot::Receiver<()>);
+
+/**
+    A guard for an advisory lock held on a file.
+
+    The lock is held by a separate thread, since acquiring it may
+    block, and the lock is released once the guard is either closed
+    or dropped, which happens when Lua garbage collects the guard.
+*/
+#[derive(Debug)]
+pub struct FsLock {
+    path: PathBuf,
+    exclusive: bool,
+    release: Mutex<Option<ReleaseChannels>>,
+}
+
+impl FsLock {
+    pub async fn close(&self) -> LuaResult<()> {
+        let channels = self.release.lock().unwrap().take();
+        let Some((release_tx, released_rx)) = channels else {
+            return Err(LuaError::runtime("Lock has already been released"));
+        };
+        // NOTE: We wait for the lock to actually be released here, so that
+        // any subsequent attempts to lock the same file can succeed right away
+        drop(release_tx);
+        released_rx.await.ok();
+        Ok(())
+    }
+}
+
+impl LuaUserData for FsLock {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "FileLock");
+        fields.add_field_method_get("path", |_, this| {
+            Ok(this.path.to_string_lossy().to_string())
+        });
+        fields.add_field_method_get("exclusive", |_, this| Ok(this.exclusive));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+}
+
+fn hold<G>(
+    guard: IoResult<G>,
+    acquired_tx: oneshot::Sender<IoResult<()>>,
+    release_rx: &mpsc::Receiver<()>,
+    released_tx: oneshot::Sender<()>,
+) {
+    match guard {
+        Ok(guard) => {
+            if acquired_tx.send(Ok(())).is_ok() {
+                // NOTE: Receiving only ever fails once the sender
+                // is dropped, which means it is time to release
+                release_rx.recv().ok();
+            }
+            drop(guard);
+            released_tx.send(()).ok();
+        }
+        Err(e) => {
+            acquired_tx.send(Err(e)).ok();
+        }
+    }
+}
+
+/**
+    Acquires an advisory lock on the file at `path`, creating it if necessary.
+
+    Returns `None` if the lock is already held elsewhere and the
+    given options say that we should not wait for it to be released.
+
+    Note that every lock that is held keeps a dedicated OS thread alive until
+    it is released, since the guard for the lock can not be moved out of the
+    thread that acquired it, and holding it in the blocking thread pool would
+    take up one of its threads for an unbounded amount of time.
+*/
+pub async fn lock(lua: &Lua, path: PathBuf, options: FsLockOptions) -> LuaResult<Option<FsLock>> {
+    let open_path = path.clone();
+    let file = lua
+        .spawn_blocking(move || {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(open_path)
+        })
+        .await
+        .map_err(|e| {
+            LuaError::RuntimeError(format!(
+                "Failed to open file for locking at '{}'\n{e}",
+                path.display()
+            ))
+        })?;
+
+    let (acquired_tx, acquired_rx) = oneshot::channel();
+    let (release_tx, release_rx) = mpsc::channel();
+    let (released_tx, released_rx) = oneshot::channel();
+
+    let FsLockOptions { exclusive, wait } = options;
+    thread::spawn(move || {
+        let mut lock: RwLock<File> = RwLock::new(file);
+        match (exclusive, wait) {
+            (true, true) => hold(lock.write(), acquired_tx, &release_rx, released_tx),
+            (true, false) => hold(lock.try_write(), acquired_tx, &release_rx, released_tx),
+            (false, true) => hold(lock.read(), acquired_tx, &release_rx, released_tx),
+            (false, false) => hold(lock.try_read(), acquired_tx, &release_rx, released_tx),
+        }
+    });
+
+    match acquired_rx.await {
+        Ok(Ok(())) => Ok(Some(FsLock {
+            path,
+            exclusive,
+            release: Mutex::new(Some((release_tx, released_rx))),
+        })),
+        Ok(Err(e)) if e.kind() == IoErrorKind::WouldBlock => Ok(None),
+        Ok(Err(e)) => Err(LuaError::RuntimeError(format!(
+            "Failed to lock file at '{}'\n{e}",
+            path.display()
+        ))),
+        Err(_) => Err(LuaError::runtime("Locking thread exited unexpectedly")),
+    }
+}
@@ -49,14 +49,14 @@ impl FromStr for FsMetadataKind {
 
 impl From<StdFileType> for FsMetadataKind {
     fn from(value: StdFileType) -> Self {
-        if value.is_file() {
-            Self::File
-        } else if value.is_dir() {
+        if value.is_dir() {
             Self::Dir
         } else if value.is_symlink() {
             Self::Symlink
         } else {
-            panic!("Encountered unknown filesystem filetype")
+            // NOTE: Special files such as sockets, fifos and devices
+            // can still be read from and written to just like files
+            Self::File
         }
     }
 }
@@ -74,20 +74,33 @@ impl<'lua> IntoLua<'lua> for FsMetadataKind {
 #[derive(Debug, Clone)]
 pub struct FsPermissions {
     pub(crate) read_only: bool,
+    pub(crate) mode: Option<u32>,
 }
 
 impl From<StdPermissions> for FsPermissions {
     fn from(value: StdPermissions) -> Self {
+        // NOTE: The raw mode also contains bits for the file type,
+        // we only keep the permission bits so that they can be
+        // passed directly to setPermissions to copy them across
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(value.mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
         Self {
             read_only: value.readonly(),
+            mode,
         }
     }
 }
 
 impl<'lua> IntoLua<'lua> for FsPermissions {
     fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
-        let tab = lua.create_table_with_capacity(0, 1)?;
+        let tab = lua.create_table_with_capacity(0, 2)?;
         tab.set("readOnly", self.read_only)?;
+        tab.set("mode", self.mode)?;
         tab.set_readonly(true);
         Ok(LuaValue::Table(tab))
     }
@@ -1,19 +1,39 @@
+use std::time::Duration;
+
 use mlua::prelude::*;
+use tokio::fs::OpenOptions;
 
-#[derive(Debug, Clone, Copy)]
-pub struct FsWriteOptions {
+use lune_std_datetime::DateTime;
+
+#[derive(Debug, Clone, Default)]
+pub struct FsWriteOptions<'lua> {
     pub(crate) overwrite: bool,
+    pub(crate) preserve_symlinks: bool,
+    pub(crate) atomic: bool,
+    pub(crate) filter: Option<LuaFunction<'lua>>,
+    pub(crate) on_progress: Option<LuaFunction<'lua>>,
 }
 
-impl<'lua> FromLua<'lua> for FsWriteOptions {
+impl<'lua> FromLua<'lua> for FsWriteOptions<'lua> {
     fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
         Ok(match value {
-            LuaValue::Nil => Self { overwrite: false },
-            LuaValue::Boolean(b) => Self { overwrite: b },
+            LuaValue::Nil => Self::default(),
+            LuaValue::Boolean(b) => Self {
+                overwrite: b,
+                ..Default::default()
+            },
             LuaValue::Table(t) => {
                 let overwrite: Option<bool> = t.get("overwrite")?;
+                let preserve_symlinks: Option<bool> = t.get("preserveSymlinks")?;
+                let atomic: Option<bool> = t.get("atomic")?;
+                let filter: Option<LuaFunction> = t.get("filter")?;
+                let on_progress: Option<LuaFunction> = t.get("onProgress")?;
                 Self {
                     overwrite: overwrite.unwrap_or(false),
+                    preserve_symlinks: preserve_symlinks.unwrap_or(false),
+                    atomic: atomic.unwrap_or(false),
+                    filter,
+                    on_progress,
                 }
             }
             _ => {
@@ -29,3 +49,352 @@ impl<'lua> FromLua<'lua> for FsWriteOptions {
         })
     }
 }
+
+#[derive(Debug, Clone, Copy)]
+pub struct FsLockOptions {
+    pub(crate) exclusive: bool,
+    pub(crate) wait: bool,
+}
+
+impl Default for FsLockOptions {
+    fn default() -> Self {
+        Self {
+            exclusive: true,
+            wait: true,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsLockOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Boolean(b) => Self {
+                exclusive: b,
+                ..Default::default()
+            },
+            LuaValue::Table(t) => {
+                let exclusive: Option<bool> = t.get("exclusive")?;
+                let wait: Option<bool> = t.get("wait")?;
+                Self {
+                    exclusive: exclusive.unwrap_or(true),
+                    wait: wait.unwrap_or(true),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsLockOptions",
+                    message: Some(format!(
+                        "Invalid lock options - expected boolean or table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FsMetadataOptions {
+    pub(crate) follow_symlinks: bool,
+}
+
+impl<'lua> FromLua<'lua> for FsMetadataOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self {
+                follow_symlinks: true,
+            },
+            LuaValue::Table(t) => {
+                let follow_symlinks: Option<bool> = t.get("followSymlinks")?;
+                Self {
+                    follow_symlinks: follow_symlinks.unwrap_or(true),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsMetadataOptions",
+                    message: Some(format!(
+                        "Invalid metadata options - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct FsOpenOptions {
+    pub(crate) read: bool,
+    pub(crate) write: bool,
+    pub(crate) append: bool,
+    pub(crate) truncate: bool,
+    pub(crate) create: bool,
+    pub(crate) create_new: bool,
+}
+
+impl FsOpenOptions {
+    pub const MODES: &'static [&'static str] = &["r", "r+", "w", "w+", "a", "a+", "x", "x+"];
+
+    fn from_mode(mode: &str) -> LuaResult<Self> {
+        let mut this = Self::default();
+        let (base, plus) = match mode.trim().strip_suffix('+') {
+            Some(base) => (base, true),
+            None => (mode.trim(), false),
+        };
+        match base {
+            "r" => this.read = true,
+            "w" => {
+                this.write = true;
+                this.create = true;
+                this.truncate = true;
+            }
+            "a" => {
+                this.append = true;
+                this.create = true;
+            }
+            "x" => {
+                this.write = true;
+                this.create_new = true;
+            }
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid open mode - got '{}', expected one of {}",
+                    mode,
+                    Self::MODES
+                        .iter()
+                        .map(|m| format!("'{m}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )))
+            }
+        }
+        if plus {
+            this.read = true;
+            this.write = !this.append;
+        }
+        Ok(this)
+    }
+
+    pub(crate) fn to_open_options(self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options
+            .read(self.read)
+            .write(self.write)
+            .append(self.append)
+            .truncate(self.truncate)
+            .create(self.create)
+            .create_new(self.create_new);
+        options
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsOpenOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self {
+                read: true,
+                ..Default::default()
+            },
+            LuaValue::String(s) => Self::from_mode(s.to_str()?)?,
+            LuaValue::Table(t) => {
+                let write: Option<bool> = t.get("write")?;
+                let append: Option<bool> = t.get("append")?;
+                let read: Option<bool> = t.get("read")?;
+                let truncate: Option<bool> = t.get("truncate")?;
+                let create: Option<bool> = t.get("create")?;
+                let create_new: Option<bool> = t.get("createNew")?;
+                let write = write.unwrap_or(false);
+                let append = append.unwrap_or(false);
+                Self {
+                    // Files are readable by default, unless only writing was requested
+                    read: read.unwrap_or(!write && !append),
+                    write,
+                    append,
+                    truncate: truncate.unwrap_or(false),
+                    create: create.unwrap_or(false),
+                    create_new: create_new.unwrap_or(false),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsOpenOptions",
+                    message: Some(format!(
+                        "Invalid open options - expected string or table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FsWatchOptions {
+    pub(crate) recursive: bool,
+    pub(crate) debounce: Duration,
+}
+
+impl Default for FsWatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            debounce: Duration::from_millis(50),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsWatchOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let mut this = Self::default();
+        match value {
+            LuaValue::Nil => {}
+            LuaValue::Table(t) => {
+                if let Some(recursive) = t.get::<_, Option<bool>>("recursive")? {
+                    this.recursive = recursive;
+                }
+                if let Some(debounce) = t.get::<_, Option<f64>>("debounce")? {
+                    this.debounce = Duration::try_from_secs_f64(debounce).map_err(|_| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid watch option 'debounce' - expected a positive number, got {debounce}"
+                        ))
+                    })?;
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsWatchOptions",
+                    message: Some(format!(
+                        "Invalid watch options - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        }
+        Ok(this)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsWalkOptions {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) gitignore: bool,
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+}
+
+fn get_patterns(t: &LuaTable, key: &str) -> LuaResult<Vec<String>> {
+    match t.get(key)? {
+        LuaValue::Nil => Ok(Vec::new()),
+        LuaValue::String(s) => Ok(vec![s.to_str()?.to_string()]),
+        LuaValue::Table(patterns) => patterns
+            .sequence_values::<String>()
+            .collect::<LuaResult<_>>(),
+        value => Err(LuaError::RuntimeError(format!(
+            "Invalid type for walk option '{key}' - expected string or array of strings, got '{}'",
+            value.type_name()
+        ))),
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsWalkOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let max_depth: Option<usize> = t.get("maxDepth")?;
+                let follow_symlinks: Option<bool> = t.get("followSymlinks")?;
+                let gitignore: Option<bool> = t.get("gitignore")?;
+                Self {
+                    max_depth,
+                    follow_symlinks: follow_symlinks.unwrap_or(false),
+                    gitignore: gitignore.unwrap_or(false),
+                    include: get_patterns(&t, "include")?,
+                    exclude: get_patterns(&t, "exclude")?,
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsWalkOptions",
+                    message: Some(format!(
+                        "Invalid walk options - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsPermissionsOptions {
+    pub(crate) mode: Option<u32>,
+    pub(crate) read_only: Option<bool>,
+}
+
+impl<'lua> FromLua<'lua> for FsPermissionsOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Table(t) => {
+                let mode: Option<u32> = t.get("mode")?;
+                let read_only: Option<bool> = t.get("readOnly")?;
+                if mode.is_some_and(|mode| mode > 0o7777) {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid permissions option 'mode' - expected a value between 0 and 0o7777, got {:#o}",
+                        mode.unwrap()
+                    )));
+                }
+                Self { mode, read_only }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsPermissionsOptions",
+                    message: Some(format!(
+                        "Invalid permissions - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsTimesOptions {
+    pub(crate) accessed: Option<DateTime>,
+    pub(crate) modified: Option<DateTime>,
+}
+
+impl<'lua> FromLua<'lua> for FsTimesOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Table(t) => {
+                let accessed: Option<LuaUserDataRef<DateTime>> = t.get("accessed")?;
+                let modified: Option<LuaUserDataRef<DateTime>> = t.get("modified")?;
+                Self {
+                    accessed: accessed.map(|a| *a),
+                    modified: modified.map(|m| *m),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsTimesOptions",
+                    message: Some(format!(
+                        "Invalid times - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
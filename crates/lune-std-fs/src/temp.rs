@@ -0,0 +1,143 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+};
+
+use mlua::prelude::*;
+use tempfile::{Builder, TempDir, TempPath};
+
+use super::metadata::FsMetadataKind;
+
+#[derive(Debug)]
+enum FsTempInner {
+    File(TempPath),
+    Dir(TempDir),
+}
+
+impl FsTempInner {
+    fn close(self) -> std::io::Result<()> {
+        match self {
+            Self::File(path) => path.close(),
+            Self::Dir(dir) => dir.close(),
+        }
+    }
+}
+
+type FsTempHandle = Arc<Mutex<Option<FsTempInner>>>;
+
+/**
+    All temporary paths that are still alive in a Lua state.
+
+    Temporary paths are removed when their handles are closed or garbage
+    collected, but garbage collection is not guaranteed to happen before
+    the program exits, so any remaining paths are removed in [`cleanup`].
+*/
+#[derive(Debug, Default)]
+struct FsTempRegistry {
+    handles: Mutex<Vec<Weak<Mutex<Option<FsTempInner>>>>>,
+}
+
+impl FsTempRegistry {
+    fn register(lua: &Lua, handle: &FsTempHandle) {
+        if lua.app_data_ref::<Self>().is_none() {
+            lua.set_app_data(Self::default());
+        }
+        let registry = lua.app_data_ref::<Self>().unwrap();
+        let mut handles = registry.handles.lock().unwrap();
+        handles.retain(|weak| weak.strong_count() > 0);
+        handles.push(Arc::downgrade(handle));
+    }
+}
+
+/**
+    Removes all temporary files and directories that are still alive in the given Lua state.
+*/
+pub fn cleanup(lua: &Lua) {
+    let Some(registry) = lua.remove_app_data::<FsTempRegistry>() else {
+        return;
+    };
+    for weak in registry.handles.into_inner().unwrap() {
+        if let Some(handle) = weak.upgrade() {
+            if let Some(inner) = handle.lock().unwrap().take() {
+                inner.close().ok();
+            }
+        }
+    }
+}
+
+/**
+    A handle to a temporary file or directory.
+
+    The path is removed when the handle is closed or garbage
+    collected, or when the runtime that created it finishes.
+*/
+#[derive(Debug)]
+pub struct FsTemp {
+    path: PathBuf,
+    kind: FsMetadataKind,
+    inner: FsTempHandle,
+}
+
+impl FsTemp {
+    fn new(lua: &Lua, inner: FsTempInner) -> Self {
+        let (path, kind) = match &inner {
+            FsTempInner::File(path) => (path.to_path_buf(), FsMetadataKind::File),
+            FsTempInner::Dir(dir) => (dir.path().to_path_buf(), FsMetadataKind::Dir),
+        };
+        let inner = Arc::new(Mutex::new(Some(inner)));
+        FsTempRegistry::register(lua, &inner);
+        Self { path, kind, inner }
+    }
+
+    pub fn close(&self) -> LuaResult<()> {
+        match self.inner.lock().unwrap().take() {
+            Some(inner) => inner.close().map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Failed to remove temporary path '{}'\n{e}",
+                    self.path.display()
+                ))
+            }),
+            None => Err(LuaError::runtime("Temporary path has already been closed")),
+        }
+    }
+}
+
+impl LuaUserData for FsTemp {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "TempPath");
+        fields.add_field_method_get("path", |_, this| {
+            Ok(this.path.to_string_lossy().to_string())
+        });
+        fields.add_field_method_get("kind", |_, this| Ok(this.kind));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("close", |_, this, (): ()| this.close());
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(this.path.to_string_lossy().to_string())
+        });
+    }
+}
+
+fn builder<'a>(prefix: Option<&'a str>, suffix: Option<&'a str>) -> Builder<'a, 'a> {
+    let mut builder = Builder::new();
+    builder.prefix(prefix.unwrap_or("lune-"));
+    if let Some(suffix) = suffix {
+        builder.suffix(suffix);
+    }
+    builder
+}
+
+pub fn temp_dir(lua: &Lua, prefix: Option<String>) -> LuaResult<FsTemp> {
+    let dir = builder(prefix.as_deref(), None).tempdir().map_err(|e| {
+        LuaError::RuntimeError(format!("Failed to create temporary directory\n{e}"))
+    })?;
+    Ok(FsTemp::new(lua, FsTempInner::Dir(dir)))
+}
+
+pub fn temp_file(lua: &Lua, prefix: Option<String>, suffix: Option<String>) -> LuaResult<FsTemp> {
+    let file = builder(prefix.as_deref(), suffix.as_deref())
+        .tempfile()
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to create temporary file\n{e}")))?;
+    Ok(FsTemp::new(lua, FsTempInner::File(file.into_temp_path())))
+}
@@ -0,0 +1,225 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use futures_lite::future::block_on;
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+
+use super::{entry::FsDirEntry, metadata::FsMetadataKind, options::FsWalkOptions};
+
+// NOTE: Entries are sent over a bounded channel so that walking
+// huge directory trees does not use more memory than necessary,
+// the walker thread will wait for Lua to catch up when it is full
+const WALK_CHANNEL_CAPACITY: usize = 256;
+
+type WalkResult = Result<FsDirEntry, String>;
+
+/**
+    A stream of directory entries, produced on a separate thread.
+*/
+#[derive(Debug, Clone)]
+pub struct FsDirWalker {
+    rx: Arc<AsyncMutex<mpsc::Receiver<WalkResult>>>,
+}
+
+impl FsDirWalker {
+    pub async fn next(&self) -> LuaResult<Option<FsDirEntry>> {
+        let mut rx = self.rx.lock().await;
+        map_walk_result(rx.recv().await)
+    }
+
+    /**
+        Gets the next entry without yielding, blocking until it has been found.
+
+        This is used for generalized iteration, since Luau does not allow yielding
+        in the iterator of a for loop - this is fine since entries are found on a
+        separate thread, which never waits for the thread that is blocked here.
+    */
+    pub fn next_blocking(&self) -> LuaResult<Option<FsDirEntry>> {
+        // NOTE: Another Lua thread may be waiting in `next`, and since it
+        // can not continue while we block, waiting for it would deadlock
+        let mut rx = self
+            .rx
+            .try_lock()
+            .map_err(|_| LuaError::runtime("Walker is already being used by another thread"))?;
+        map_walk_result(block_on(rx.recv()))
+    }
+}
+
+fn map_walk_result(result: Option<WalkResult>) -> LuaResult<Option<FsDirEntry>> {
+    match result {
+        Some(Ok(entry)) => Ok(Some(entry)),
+        Some(Err(e)) => Err(LuaError::RuntimeError(e)),
+        None => Ok(None),
+    }
+}
+
+impl LuaUserData for FsDirWalker {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("next", |_, this, (): ()| async move { this.next().await });
+        methods.add_meta_method(LuaMetaMethod::Iter, |lua, this, (): ()| {
+            let walker = this.clone();
+            lua.create_function(move |_, (): ()| walker.next_blocking())
+        });
+    }
+
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "DirWalker");
+    }
+}
+
+fn build_glob_set(patterns: &[String], option_name: &str) -> LuaResult<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| {
+            LuaError::RuntimeError(format!(
+                "Invalid glob pattern '{pattern}' in walk option '{option_name}'\n{e}"
+            ))
+        })?;
+        builder.add(glob);
+    }
+    builder.build().map(Some).into_lua_err()
+}
+
+/**
+    Walks the directory at `root` on a separate thread.
+
+    Entries are yielded with paths relative to the current working
+    directory, the same way as `root` was given, and are matched
+    against include & exclude patterns relative to `root` itself.
+*/
+pub fn walk_dir(lua: &Lua, root: PathBuf, options: FsWalkOptions) -> LuaResult<FsDirWalker> {
+    walk(lua, root, options, None)
+}
+
+/**
+    Finds all paths matching the given glob pattern.
+
+    The directory to start walking from is the longest leading
+    part of the pattern that does not contain any glob syntax.
+
+    Unless the pattern contains `**`, paths can never match it if they are
+    deeper than the pattern itself, so the walk does not go any deeper.
+*/
+pub fn glob(lua: &Lua, pattern: String, mut options: FsWalkOptions) -> LuaResult<FsDirWalker> {
+    let matcher = GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid glob pattern '{pattern}'\n{e}")))?
+        .compile_matcher();
+
+    let mut root = PathBuf::new();
+    for component in Path::new(&pattern).components() {
+        let is_literal = match component {
+            Component::Normal(s) => !s.to_string_lossy().contains(['*', '?', '[', '{']),
+            _ => true,
+        };
+        if !is_literal {
+            break;
+        }
+        root.push(component);
+    }
+
+    // Patterns without any glob syntax match a single
+    // path, which we find by walking its parent directory
+    if root == Path::new(&pattern) {
+        root.pop();
+    }
+
+    if !pattern.contains("**") {
+        let pattern_depth = Path::new(&pattern).components().count();
+        let depth = pattern_depth.saturating_sub(root.components().count());
+        options.max_depth = Some(options.max_depth.map_or(depth, |max| max.min(depth)));
+    }
+
+    walk(lua, root, options, Some(matcher))
+}
+
+fn walk(
+    lua: &Lua,
+    root: PathBuf,
+    options: FsWalkOptions,
+    matcher: Option<GlobMatcher>,
+) -> LuaResult<FsDirWalker> {
+    let include = build_glob_set(&options.include, "include")?;
+    let exclude = build_glob_set(&options.exclude, "exclude")?;
+
+    let walk_root = if root.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        root.clone()
+    };
+    if !walk_root.exists() {
+        return Err(LuaError::RuntimeError(format!(
+            "No directory exists at the path '{}'",
+            walk_root.display()
+        )));
+    }
+
+    let mut builder = WalkBuilder::new(&walk_root);
+    builder
+        .max_depth(options.max_depth)
+        .follow_links(options.follow_symlinks)
+        .hidden(false)
+        .parents(options.gitignore)
+        .ignore(options.gitignore)
+        .git_ignore(options.gitignore)
+        .git_exclude(options.gitignore)
+        .git_global(false)
+        .require_git(false)
+        .sort_by_file_name(Ord::cmp);
+    if let Some(exclude) = exclude {
+        let filter_root = walk_root.clone();
+        builder.filter_entry(move |entry| match entry.path().strip_prefix(&filter_root) {
+            Ok(relative) => !exclude.is_match(relative),
+            Err(_) => true,
+        });
+    }
+
+    let (tx, rx) = mpsc::channel(WALK_CHANNEL_CAPACITY);
+    lua.spawn_blocking(move || {
+        for result in builder.build() {
+            let item = match result {
+                // The root itself is not an entry *in* the directory, skip it
+                Ok(entry) if entry.depth() == 0 => continue,
+                Ok(entry) => {
+                    let relative = entry
+                        .path()
+                        .strip_prefix(&walk_root)
+                        .unwrap_or(entry.path())
+                        .to_path_buf();
+                    if include.as_ref().is_some_and(|i| !i.is_match(&relative)) {
+                        continue;
+                    }
+                    let path = root.join(&relative);
+                    if matcher.as_ref().is_some_and(|m| !m.is_match(&path)) {
+                        continue;
+                    }
+                    let kind = entry
+                        .file_type()
+                        .map_or(FsMetadataKind::File, FsMetadataKind::from);
+                    Ok(FsDirEntry::new(path, kind, entry.depth()))
+                }
+                Err(e) => Err(format!("Failed to walk directory\n{e}")),
+            };
+            // NOTE: Sending only fails if the walker was dropped (garbage
+            // collected) in Lua, meaning nobody is interested in the results
+            if tx.blocking_send(item).is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+
+    Ok(FsDirWalker {
+        rx: Arc::new(AsyncMutex::new(rx)),
+    })
+}
@@ -0,0 +1,241 @@
+use std::{
+    path::PathBuf,
+    rc::{Rc, Weak},
+};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use tokio::{
+    sync::{mpsc, watch},
+    time::{sleep_until, Instant},
+};
+
+use lune_utils::TableBuilder;
+
+use super::options::FsWatchOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WatchEventKind {
+    Create,
+    Modify,
+    Remove,
+    Rename,
+}
+
+impl WatchEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Remove => "remove",
+            Self::Rename => "rename",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WatchEvent {
+    kind: WatchEventKind,
+    path: PathBuf,
+    from: Option<PathBuf>,
+}
+
+impl WatchEvent {
+    fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        TableBuilder::new(lua)?
+            .with_value("kind", self.kind.as_str())?
+            .with_value("path", self.path.to_string_lossy().to_string())?
+            .with_value(
+                "from",
+                self.from.map(|from| from.to_string_lossy().to_string()),
+            )?
+            .build_readonly()
+    }
+}
+
+/**
+    Pending events that have not yet been delivered to Lua.
+
+    Events are collected here for the duration of the debounce
+    period, so that bursts of changes to the same paths can
+    be coalesced into a smaller set of meaningful events.
+*/
+#[derive(Debug, Default)]
+struct WatchEventBatch {
+    events: Vec<WatchEvent>,
+    renamed_from: Vec<PathBuf>,
+    renamed_to: Vec<PathBuf>,
+}
+
+impl WatchEventBatch {
+    fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.renamed_from.is_empty() && self.renamed_to.is_empty()
+    }
+
+    fn push_event(&mut self, kind: WatchEventKind, path: PathBuf, from: Option<PathBuf>) {
+        // A file that was just created will also get modified when written to,
+        // but the creation event is the only one that is interesting to receive
+        if kind == WatchEventKind::Modify
+            && self
+                .events
+                .iter()
+                .any(|e| e.kind == WatchEventKind::Create && e.path == path)
+        {
+            return;
+        }
+        let event = WatchEvent { kind, path, from };
+        if !self.events.contains(&event) {
+            self.events.push(event);
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        let mut paths = event.paths.into_iter();
+        match event.kind {
+            EventKind::Create(_) => {
+                for path in paths {
+                    self.push_event(WatchEventKind::Create, path, None);
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                    self.renamed_from.retain(|p| p != &from);
+                    self.renamed_to.retain(|p| p != &to);
+                    self.push_event(WatchEventKind::Rename, to, Some(from));
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                self.renamed_from.extend(paths);
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                self.renamed_to.extend(paths);
+            }
+            EventKind::Modify(_) => {
+                for path in paths {
+                    self.push_event(WatchEventKind::Modify, path, None);
+                }
+            }
+            EventKind::Remove(_) => {
+                for path in paths {
+                    self.push_event(WatchEventKind::Remove, path, None);
+                }
+            }
+            EventKind::Any | EventKind::Access(_) | EventKind::Other => {}
+        }
+    }
+
+    fn take(&mut self) -> Vec<WatchEvent> {
+        // Renames that were never paired up moved a path into or out
+        // of the watched directory, which looks like a create or remove
+        for path in std::mem::take(&mut self.renamed_from) {
+            self.push_event(WatchEventKind::Remove, path, None);
+        }
+        for path in std::mem::take(&mut self.renamed_to) {
+            self.push_event(WatchEventKind::Create, path, None);
+        }
+        std::mem::take(&mut self.events)
+    }
+}
+
+pub fn watch<'lua>(
+    lua: &'lua Lua,
+    path: String,
+    options: FsWatchOptions,
+    callback: LuaFunction<'lua>,
+) -> LuaResult<LuaTable<'lua>> {
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let mut watcher = RecommendedWatcher::new(
+        move |res: notify::Result<Event>| {
+            // NOTE: Errors here mean the receiving end was
+            // dropped, so the watcher is about to stop anyway
+            if let Ok(event) = res {
+                event_tx.send(event).ok();
+            }
+        },
+        notify::Config::default(),
+    )
+    .into_lua_err()?;
+
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(PathBuf::from(&path).as_path(), mode)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to watch path '{path}'\n{e}")))?;
+
+    let lua_inner = lua
+        .app_data_ref::<Weak<Lua>>()
+        .expect("Missing weak lua ref")
+        .upgrade()
+        .expect("Lua was dropped unexpectedly");
+    let callback_key = lua.create_registry_value(callback)?;
+    let debounce = options.debounce;
+
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    lua.spawn_local(async move {
+        // NOTE: The watcher must be kept alive for as long
+        // as we want to receive events, so we move it here
+        let _watcher = watcher;
+        let mut batch = WatchEventBatch::default();
+        let mut deadline: Option<Instant> = None;
+        let mut detached = false;
+        loop {
+            let fut_flush = async {
+                match deadline {
+                    Some(deadline) => sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                event = event_rx.recv() => match event {
+                    Some(event) => {
+                        batch.push(event);
+                        if deadline.is_none() && !batch.is_empty() {
+                            deadline = Some(Instant::now() + debounce);
+                        }
+                    }
+                    None => break,
+                },
+                () = fut_flush => {
+                    deadline = None;
+                    for event in batch.take() {
+                        if let Err(e) = deliver(&lua_inner, &callback_key, event) {
+                            lua_inner.report_error(&e);
+                        }
+                    }
+                },
+                res = shutdown_rx.changed(), if !detached => {
+                    // NOTE: We will only get a RecvError here if the watch handle is dropped,
+                    // this means lua has garbage collected it and the user does not want
+                    // to manually stop the watcher using the watch handle. Run forever.
+                    if res.is_ok() {
+                        break;
+                    }
+                    detached = true;
+                },
+            }
+        }
+        lua_inner.remove_registry_value(callback_key).ok();
+    });
+
+    TableBuilder::new(lua)?
+        .with_value("path", path)?
+        .with_function("stop", move |_, (): ()| match shutdown_tx.send(true) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(LuaError::runtime("Watcher already stopped")),
+        })?
+        .build_readonly()
+}
+
+fn deliver(lua: &Rc<Lua>, callback_key: &LuaRegistryKey, event: WatchEvent) -> LuaResult<()> {
+    let callback: LuaFunction = lua.registry_value(callback_key)?;
+    let event = event.into_lua_table(lua)?;
+    lua.push_thread_back(callback, event)?;
+    Ok(())
+}
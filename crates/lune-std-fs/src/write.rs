@@ -0,0 +1,108 @@
+use std::{
+    collections::hash_map::RandomState,
+    ffi::OsString,
+    hash::{BuildHasher, Hasher},
+    io::{ErrorKind as IoErrorKind, Result as IoResult},
+    path::{Path, PathBuf},
+};
+
+use mlua::prelude::*;
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+use super::options::FsWriteOptions;
+
+const TEMP_FILE_ATTEMPTS: usize = 8;
+
+fn temp_sibling_path(path: &Path) -> PathBuf {
+    // NOTE: RandomState is seeded randomly for every instance,
+    // which is plenty random enough to avoid name collisions
+    let random = RandomState::new().build_hasher().finish();
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{random:016x}.tmp"));
+    path.with_file_name(name)
+}
+
+/**
+    Writes `contents` to a file at `path`.
+
+    If the atomic option is set, contents are first written to a
+    temporary file next to the target path, synced to disk, and then
+    renamed over the target - meaning that the target will either
+    contain its old contents or the new contents, but never a mix.
+*/
+pub async fn write_file(
+    path: &Path,
+    contents: &[u8],
+    options: FsWriteOptions<'_>,
+) -> LuaResult<()> {
+    if !options.atomic {
+        return fs::write(path, contents).await.into_lua_err();
+    }
+
+    let mut attempts = 0;
+    let (temp_path, mut temp_file) = loop {
+        let temp_path = temp_sibling_path(path);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+            .await
+        {
+            Ok(file) => break (temp_path, file),
+            Err(e) if e.kind() == IoErrorKind::AlreadyExists && attempts < TEMP_FILE_ATTEMPTS => {
+                attempts += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    let result = async {
+        temp_file.write_all(contents).await?;
+        temp_file.sync_all().await?;
+        drop(temp_file);
+
+        // Keep the permissions of any file we are replacing, the
+        // temporary file was created with default permissions
+        match fs::metadata(path).await {
+            Ok(meta) => fs::set_permissions(&temp_path, meta.permissions()).await?,
+            Err(e) if e.kind() == IoErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        fs::rename(&temp_path, path).await?;
+        sync_parent_dir(path).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        fs::remove_file(&temp_path).await.ok();
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/**
+    Syncs the directory containing `path`, which makes sure that a
+    rename into that directory is durable, and not only the file itself.
+
+    This is only possible on Unix, where directories can be opened as files.
+*/
+#[cfg(unix)]
+async fn sync_parent_dir(path: &Path) -> IoResult<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::File::open(parent).await?.sync_all().await
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unused_async)]
+async fn sync_parent_dir(_: &Path) -> IoResult<()> {
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use std::{
     collections::HashMap,
+    fs,
+    io::Result as IoResult,
     net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
 };
 
 use bstr::{BString, ByteSlice};
@@ -10,6 +13,8 @@ use reqwest::Method;
 
 use super::util::table_to_hash_map;
 
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
 const DEFAULT_IP_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
 const WEB_SOCKET_UPDGRADE_REQUEST_HANDLER: &str = r#"
@@ -156,6 +161,85 @@ impl FromLua<'_> for RequestConfig {
     }
 }
 
+// Net serve TLS config
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsSource {
+    File(PathBuf),
+    Pem(Vec<u8>),
+}
+
+impl TlsSource {
+    pub fn read(&self) -> IoResult<Vec<u8>> {
+        match self {
+            Self::File(path) => fs::read(path),
+            Self::Pem(pem) => Ok(pem.clone()),
+        }
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            Self::File(path) => Some(path),
+            Self::Pem(_) => None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for TlsSource {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::String(s) = &value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "TlsSource",
+                message: Some(format!(
+                    "Invalid tls config - expected file path or PEM string, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+        // NOTE: Anything that contains a PEM header is treated as the contents of
+        // a PEM file, since file paths will practically never contain one of those
+        let bytes = s.as_bytes();
+        if bytes.find(b"-----BEGIN ").is_some() {
+            Ok(Self::Pem(bytes.to_vec()))
+        } else {
+            Ok(Self::File(PathBuf::from(s.to_str()?)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServeTlsConfig {
+    pub cert: TlsSource,
+    pub key: TlsSource,
+}
+
+impl<'lua> FromLua<'lua> for ServeTlsConfig {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(t) = &value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ServeTlsConfig",
+                message: Some(format!(
+                    "Invalid tls config - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+        let cert: LuaValue = t.get("cert")?;
+        let key: LuaValue = t.get("key")?;
+        if cert.is_nil() || key.is_nil() {
+            return Err(LuaError::runtime(
+                "Invalid tls config - expected both 'cert' and 'key' to be given",
+            ));
+        }
+        Ok(Self {
+            cert: TlsSource::from_lua(cert, lua)?,
+            key: TlsSource::from_lua(key, lua)?,
+        })
+    }
+}
+
 // Net serve config
 
 #[derive(Debug)]
@@ -163,6 +247,23 @@ pub struct ServeConfig<'a> {
     pub address: IpAddr,
     pub handle_request: LuaFunction<'a>,
     pub handle_web_socket: Option<LuaFunction<'a>>,
+    pub tls: Option<ServeTlsConfig>,
+    pub max_body_size: Option<usize>,
+    pub stream_body: bool,
+}
+
+fn parse_max_body_size(t: &LuaTable) -> LuaResult<Option<usize>> {
+    match t.get::<_, Option<LuaNumber>>("maxBodySize")? {
+        None => Ok(None),
+        Some(n) if n.fract() == 0.0 && (0.0..=MAX_SAFE_INTEGER).contains(&n) =>
+        {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            Ok(Some(usize::try_from(n as u64).unwrap_or(usize::MAX)))
+        }
+        Some(n) => Err(LuaError::RuntimeError(format!(
+            "Invalid serve config - expected 'maxBodySize' to be a positive integer, got {n}"
+        ))),
+    }
 }
 
 impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
@@ -173,12 +274,18 @@ impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
                 handle_request: f.clone(),
                 handle_web_socket: None,
                 address: DEFAULT_IP_ADDRESS,
+                tls: None,
+                max_body_size: None,
+                stream_body: false,
             })
         } else if let LuaValue::Table(t) = &value {
             // Table means custom options
             let address: Option<LuaString> = t.get("address")?;
             let handle_request: Option<LuaFunction> = t.get("handleRequest")?;
             let handle_web_socket: Option<LuaFunction> = t.get("handleWebSocket")?;
+            let tls: Option<ServeTlsConfig> = t.get("tls")?;
+            let max_body_size = parse_max_body_size(t)?;
+            let stream_body: Option<bool> = t.get("streamBody")?;
             if handle_request.is_some() || handle_web_socket.is_some() {
                 let address: IpAddr = match &address {
                     Some(addr) => {
@@ -209,6 +316,9 @@ impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
                             .expect("Failed to create default http responder function")
                     }),
                     handle_web_socket,
+                    tls,
+                    max_body_size,
+                    stream_body: stream_body.unwrap_or_default(),
                 })
             } else {
                 Err(LuaError::FromLuaConversionError {
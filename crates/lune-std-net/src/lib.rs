@@ -15,7 +15,7 @@ use lune_utils::TableBuilder;
 use self::{
     client::{NetClient, NetClientBuilder},
     config::{RequestConfig, ServeConfig},
-    server::serve,
+    server::{create_event_stream, serve},
     util::create_user_agent_header,
     websocket::NetWebSocket,
 };
@@ -40,6 +40,7 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
         .with_async_function("request", net_request)?
         .with_async_function("socket", net_socket)?
         .with_async_function("serve", net_serve)?
+        .with_function("eventStream", create_event_stream)?
         .with_function("urlEncode", net_url_encode)?
         .with_function("urlDecode", net_url_decode)?
         .build_readonly()
@@ -0,0 +1,36 @@
+use std::{future::Future, rc::Rc};
+
+use hyper::rt::Executor;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+
+/**
+    An executor for hyper that spawns futures on the Lua scheduler.
+
+    HTTP/2 connections spawn a separate future for each of their streams,
+    and these futures call into Lua, meaning they are not `Send` and must
+    run on the same thread as the scheduler, unlike with the tokio executor.
+*/
+#[derive(Debug, Clone)]
+pub(super) struct LocalExecutor {
+    lua: Rc<Lua>,
+}
+
+impl LocalExecutor {
+    pub fn new(lua: Rc<Lua>) -> Self {
+        Self { lua }
+    }
+}
+
+impl<F> Executor<F> for LocalExecutor
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    fn execute(&self, fut: F) {
+        self.lua.spawn_local(async move {
+            fut.await;
+        });
+    }
+}
@@ -1,11 +1,17 @@
 use std::{
     net::SocketAddr,
     rc::{Rc, Weak},
+    time::Duration,
 };
 
-use hyper::server::conn::http1;
-use hyper_util::rt::TokioIo;
-use tokio::{net::TcpListener, pin};
+use hyper_util::{rt::TokioIo, server::conn::auto};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    pin,
+    sync::watch,
+    time::timeout,
+};
 
 use mlua::prelude::*;
 use mlua_luau_scheduler::LuaSpawnExt;
@@ -14,13 +20,22 @@ use lune_utils::TableBuilder;
 
 use super::config::ServeConfig;
 
+mod executor;
 mod keys;
 mod request;
 mod response;
 mod service;
+mod stream;
+mod tls;
 
+use executor::LocalExecutor;
 use keys::SvcKeys;
-use service::Svc;
+use service::{Svc, SvcOptions};
+use tls::create_tls_acceptor;
+
+pub use stream::create_event_stream;
+
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub async fn serve<'lua>(
     lua: &'lua Lua,
@@ -28,6 +43,10 @@ pub async fn serve<'lua>(
     config: ServeConfig<'lua>,
 ) -> LuaResult<LuaTable<'lua>> {
     let addr: SocketAddr = (config.address, port).into();
+    let tls_acceptor = config
+        .tls
+        .map(|tls| create_tls_acceptor(lua, tls))
+        .transpose()?;
     let listener = TcpListener::bind(addr).await?;
 
     let (lua_svc, lua_inner) = {
@@ -39,41 +58,49 @@ pub async fn serve<'lua>(
         (Rc::clone(&rc), rc)
     };
 
+    // NOTE: The auto builder serves both HTTP/1.1 and HTTP/2 on the same port,
+    // detecting HTTP/2 using its connection preface, which works both for h2c
+    // with prior knowledge and for h2 that was negotiated using ALPN with TLS
+    let mut builder = auto::Builder::new(LocalExecutor::new(Rc::clone(&lua_inner)));
+    builder.http1().keep_alive(true); // Web sockets need this
+
     let keys = SvcKeys::new(lua, config.handle_request, config.handle_web_socket)?;
-    let svc = Svc {
-        lua: lua_svc,
-        addr,
-        keys,
+    let options = SvcOptions {
+        max_body_size: config.max_body_size,
+        stream_body: config.stream_body,
     };
 
-    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
     lua.spawn_local(async move {
         let mut shutdown_rx_outer = shutdown_rx.clone();
         loop {
             // Create futures for accepting new connections and shutting down
             let fut_shutdown = shutdown_rx_outer.changed();
             let fut_accept = async {
-                let stream = match listener.accept().await {
-                    Err(_) => return,
-                    Ok((s, _)) => s,
+                let Ok((stream, remote_addr)) = listener.accept().await else {
+                    return;
                 };
 
-                let io = TokioIo::new(stream);
-                let svc = svc.clone();
-                let mut shutdown_rx_inner = shutdown_rx.clone();
+                let svc = Svc {
+                    lua: Rc::clone(&lua_svc),
+                    remote_addr,
+                    keys,
+                    options,
+                };
+                let builder = builder.clone();
+                let shutdown_rx_inner = shutdown_rx.clone();
+                let tls_acceptor = tls_acceptor.clone();
 
                 lua_inner.spawn_local(async move {
-                    let conn = http1::Builder::new()
-                        .keep_alive(true) // Web sockets need this
-                        .serve_connection(io, svc)
-                        .with_upgrades();
-                    // NOTE: Because we need to use keep_alive for websockets, we need to
-                    // also manually poll this future and handle the shutdown signal here
-                    pin!(conn);
-                    tokio::select! {
-                        _ = conn.as_mut() => {}
-                        _ = shutdown_rx_inner.changed() => {
-                            conn.as_mut().graceful_shutdown();
+                    match tls_acceptor {
+                        None => serve_connection(&builder, stream, svc, shutdown_rx_inner).await,
+                        Some(acceptor) => {
+                            // NOTE: Failed or stalled handshakes are the fault of the client,
+                            // and should not affect the server or any other connections
+                            let handshake = timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream));
+                            if let Ok(Ok(stream)) = handshake.await {
+                                serve_connection(&builder, stream, svc, shutdown_rx_inner).await;
+                            }
                         }
                     }
                 });
@@ -103,3 +130,25 @@ pub async fn serve<'lua>(
         })?
         .build_readonly()
 }
+
+async fn serve_connection<S>(
+    builder: &auto::Builder<LocalExecutor>,
+    stream: S,
+    svc: Svc,
+    mut shutdown_rx: watch::Receiver<bool>,
+) where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    // NOTE: Web socket upgrades are only supported for HTTP/1.1
+    // connections, HTTP/2 connections will never try to upgrade
+    let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), svc);
+    // NOTE: Because we need to use keep_alive for websockets, we need to
+    // also manually poll this future and handle the shutdown signal here
+    pin!(conn);
+    tokio::select! {
+        _ = conn.as_mut() => {}
+        _ = shutdown_rx.changed() => {
+            conn.as_mut().graceful_shutdown();
+        }
+    }
+}
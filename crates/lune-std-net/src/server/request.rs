@@ -1,36 +1,77 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::{cell::Cell, collections::HashMap, error::Error, net::SocketAddr, rc::Rc};
 
-use http::request::Parts;
+use http::{header::CONTENT_LENGTH, request::Parts, Version};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::body::Incoming;
+use tokio::sync::Mutex as AsyncMutex;
 
 use mlua::prelude::*;
 
 use lune_utils::TableBuilder;
 
+/**
+    The body of a request to `net.serve`, limited to a maximum size.
+*/
+pub(super) type LimitedBody = Limited<Incoming>;
+
+/**
+    Gets the length of the body of a request, if the client sent one.
+*/
+pub(super) fn content_length(head: &Parts) -> Option<u64> {
+    head.headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/**
+    Reads the full body of a request.
+
+    Returns `Ok(None)` if the body was larger than its maximum size.
+*/
+pub(super) async fn collect_body(body: LimitedBody) -> LuaResult<Option<Vec<u8>>> {
+    match body.collect().await {
+        Ok(collected) => Ok(Some(collected.to_bytes().to_vec())),
+        Err(e) if e.downcast_ref::<LengthLimitError>().is_some() => Ok(None),
+        Err(e) => Err(LuaError::external(e)),
+    }
+}
+
+pub(super) enum LuaRequestBody {
+    Bytes(Vec<u8>),
+    Stream(RequestBodyStream),
+}
+
 pub(super) struct LuaRequest {
-    pub(super) _remote_addr: SocketAddr,
+    pub(super) remote_addr: SocketAddr,
     pub(super) head: Parts,
-    pub(super) body: Vec<u8>,
+    pub(super) body: LuaRequestBody,
 }
 
 impl LuaRequest {
     pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
         let method = self.head.method.as_str().to_string();
         let path = self.head.uri.path().to_string();
-        let body = lua.create_string(&self.body)?;
+        let version = version_name(self.head.version);
+        let raw_query = self.head.uri.query().unwrap_or_default();
+        let body = match self.body {
+            LuaRequestBody::Bytes(bytes) => LuaValue::String(lua.create_string(bytes)?),
+            LuaRequestBody::Stream(stream) => LuaValue::UserData(lua.create_userdata(stream)?),
+        };
 
-        let query: HashMap<LuaString, LuaString> = self
-            .head
-            .uri
-            .query()
-            .unwrap_or_default()
-            .split('&')
-            .filter_map(|q| q.split_once('='))
-            .map(|(k, v)| {
-                let k = lua.create_string(k)?;
-                let v = lua.create_string(v)?;
-                Ok((k, v))
-            })
-            .collect::<LuaResult<_>>()?;
+        let query = lua.create_table()?;
+        for (key, mut values) in parse_query(raw_query) {
+            let key = lua.create_string(key)?;
+            if values.len() == 1 {
+                query.raw_set(key, lua.create_string(values.remove(0))?)?;
+            } else {
+                let values = values
+                    .into_iter()
+                    .map(|value| lua.create_string(value))
+                    .collect::<LuaResult<Vec<_>>>()?;
+                query.raw_set(key, values)?;
+            }
+        }
 
         let headers: HashMap<LuaString, LuaString> = self
             .head
@@ -46,9 +87,149 @@ impl LuaRequest {
         TableBuilder::new(lua)?
             .with_value("method", method)?
             .with_value("path", path)?
+            .with_value("version", version)?
             .with_value("query", query)?
+            .with_value("rawQuery", raw_query)?
             .with_value("headers", headers)?
             .with_value("body", body)?
+            .with_value("remoteAddress", self.remote_addr.ip().to_string())?
+            .with_value("remotePort", self.remote_addr.port())?
             .build()
     }
 }
+
+/**
+    Parses and percent-decodes the pairs in a query string, grouping
+    the values of any keys that are given more than once, in order.
+
+    Keys without a value, such as `flag` in `?flag&key=value`,
+    are given an empty string as their value.
+*/
+fn parse_query(raw_query: &str) -> Vec<(Vec<u8>, Vec<Vec<u8>>)> {
+    let mut order: Vec<Vec<u8>> = Vec::new();
+    let mut values: HashMap<Vec<u8>, Vec<Vec<u8>>> = HashMap::new();
+    for pair in raw_query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let (key, value) = (decode_query_component(key), decode_query_component(value));
+        if let Some(existing) = values.get_mut(&key) {
+            existing.push(value);
+        } else {
+            order.push(key.clone());
+            values.insert(key, vec![value]);
+        }
+    }
+    order
+        .into_iter()
+        .map(|key| {
+            let key_values = values.remove(&key).unwrap_or_default();
+            (key, key_values)
+        })
+        .collect()
+}
+
+fn version_name(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_2 => "HTTP/2.0",
+        Version::HTTP_3 => "HTTP/3.0",
+        // NOTE: Version is not an enum, so the compiler can not know
+        // that the above are all the versions that actually exist, and
+        // this arm covers any others too, including HTTP/1.1
+        _ => "HTTP/1.1",
+    }
+}
+
+fn decode_query_component(component: &str) -> Vec<u8> {
+    // NOTE: Query strings sent by html forms encode spaces as plus
+    // signs, and literal plus signs are always percent-encoded
+    let component = component.replace('+', " ");
+    urlencoding::decode_binary(component.as_bytes()).into_owned()
+}
+
+/**
+    A userdata for reading the body of a request in chunks, as it is received.
+*/
+#[derive(Debug)]
+pub(super) struct RequestBodyStream {
+    inner: AsyncMutex<LimitedBody>,
+    max_size: usize,
+    limit_exceeded: Rc<Cell<bool>>,
+}
+
+impl RequestBodyStream {
+    pub(super) fn new(body: LimitedBody, max_size: usize) -> Self {
+        Self {
+            inner: AsyncMutex::new(body),
+            max_size,
+            limit_exceeded: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /**
+        Gets a flag that is set once reading has failed because
+        the body was larger than its maximum size, which stays
+        available after the stream itself has been given to Lua.
+    */
+    pub(super) fn limit_exceeded(&self) -> Rc<Cell<bool>> {
+        Rc::clone(&self.limit_exceeded)
+    }
+
+    fn map_error(&self, e: &(dyn Error + Send + Sync + 'static)) -> LuaError {
+        if e.is::<LengthLimitError>() {
+            self.limit_exceeded.set(true);
+            LuaError::RuntimeError(format!(
+                "Request body exceeds the maximum size of {} bytes",
+                self.max_size
+            ))
+        } else {
+            LuaError::RuntimeError(format!("Failed to read request body\n{e}"))
+        }
+    }
+
+    async fn read(&self) -> LuaResult<Option<Vec<u8>>> {
+        let mut body = self.inner.lock().await;
+        loop {
+            match body.frame().await {
+                None => return Ok(None),
+                Some(Err(e)) => return Err(self.map_error(e.as_ref())),
+                Some(Ok(frame)) => {
+                    // NOTE: Trailers and empty chunks are skipped, since
+                    // returning an empty chunk would look like the end
+                    if let Ok(data) = frame.into_data() {
+                        if !data.is_empty() {
+                            return Ok(Some(data.to_vec()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn read_all(&self) -> LuaResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.read().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes)
+    }
+}
+
+impl LuaUserData for RequestBodyStream {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "RequestBody");
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("read", |lua, this, (): ()| async move {
+            match this.read().await? {
+                Some(chunk) => Ok(LuaValue::String(lua.create_string(chunk)?)),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+
+        methods.add_async_method("readAll", |lua, this, (): ()| async move {
+            lua.create_string(this.read_all().await?)
+        });
+    }
+}
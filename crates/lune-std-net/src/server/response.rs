@@ -1,40 +1,82 @@
-use std::str::FromStr;
+use std::{rc::Rc, str::FromStr};
 
-use bstr::{BString, ByteSlice};
-use http_body_util::Full;
+use bstr::BString;
 use hyper::{
-    body::Bytes,
     header::{HeaderName, HeaderValue},
     HeaderMap, Response,
 };
 
 use mlua::prelude::*;
 
+#[cfg(feature = "fs")]
+use lune_std_fs::FsFile;
+
+use super::stream::{EventStream, ResponseBody, ResponseStreamSource};
+
 #[derive(Debug, Clone, Copy)]
 pub(super) enum LuaResponseKind {
     PlainText,
     Table,
 }
 
+pub(super) enum LuaResponseBody {
+    Bytes(Vec<u8>),
+    Stream(ResponseStreamSource),
+}
+
+impl LuaResponseBody {
+    fn into_body(self, lua: &Rc<Lua>) -> ResponseBody {
+        match self {
+            Self::Bytes(bytes) => ResponseBody::from(bytes),
+            Self::Stream(source) => source.spawn(lua),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for LuaResponseBody {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            // Functions are called repeatedly to stream the body in chunks
+            LuaValue::Function(f) => Ok(Self::Stream(ResponseStreamSource::Callback {
+                callback: lua.create_registry_value(f)?,
+                keep_alive: None,
+            })),
+            // Files and event streams are also streamed, anything else is sent as-is
+            #[cfg(feature = "fs")]
+            LuaValue::UserData(ud) if ud.is::<FsFile>() => Ok(Self::Stream(
+                ResponseStreamSource::File(ud.borrow::<FsFile>()?.clone()),
+            )),
+            LuaValue::UserData(ud) if ud.is::<EventStream>() => {
+                Ok(Self::Stream(ud.borrow::<EventStream>()?.to_source(lua)?))
+            }
+            value => Ok(Self::Bytes(BString::from_lua(value, lua)?.into())),
+        }
+    }
+}
+
 pub(super) struct LuaResponse {
     pub(super) kind: LuaResponseKind,
     pub(super) status: u16,
     pub(super) headers: HeaderMap,
-    pub(super) body: Option<Vec<u8>>,
+    pub(super) body: Option<LuaResponseBody>,
 }
 
 impl LuaResponse {
-    pub(super) fn into_response(self) -> LuaResult<Response<Full<Bytes>>> {
+    pub(super) fn into_response(self, lua: &Rc<Lua>) -> LuaResult<Response<ResponseBody>> {
+        let body = self
+            .body
+            .unwrap_or(LuaResponseBody::Bytes(Vec::new()))
+            .into_body(lua);
         Ok(match self.kind {
             LuaResponseKind::PlainText => Response::builder()
                 .status(200)
                 .header("Content-Type", "text/plain")
-                .body(Full::new(Bytes::from(self.body.unwrap())))
+                .body(body)
                 .into_lua_err()?,
             LuaResponseKind::Table => {
                 let mut response = Response::builder()
                     .status(self.status)
-                    .body(Full::new(Bytes::from(self.body.unwrap_or_default())))
+                    .body(body)
                     .into_lua_err()?;
                 response.headers_mut().extend(self.headers);
                 response
@@ -43,21 +85,21 @@ impl LuaResponse {
     }
 }
 
-impl FromLua<'_> for LuaResponse {
-    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+impl<'lua> FromLua<'lua> for LuaResponse {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         match value {
             // Plain strings from the handler are plaintext responses
             LuaValue::String(s) => Ok(Self {
                 kind: LuaResponseKind::PlainText,
                 status: 200,
                 headers: HeaderMap::new(),
-                body: Some(s.as_bytes().to_vec()),
+                body: Some(LuaResponseBody::Bytes(s.as_bytes().to_vec())),
             }),
             // Tables are more detailed responses with potential status, headers, body
             LuaValue::Table(t) => {
                 let status: Option<u16> = t.get("status")?;
                 let headers: Option<LuaTable> = t.get("headers")?;
-                let body: Option<BString> = t.get("body")?;
+                let body: LuaValue = t.get("body")?;
 
                 let mut headers_map = HeaderMap::new();
                 if let Some(headers) = headers {
@@ -69,13 +111,16 @@ impl FromLua<'_> for LuaResponse {
                     }
                 }
 
-                let body_bytes = body.map(|s| s.as_bytes().to_vec());
+                let body = match body {
+                    LuaValue::Nil => None,
+                    body => Some(LuaResponseBody::from_lua(body, lua)?),
+                };
 
                 Ok(Self {
                     kind: LuaResponseKind::Table,
                     status: status.unwrap_or(200),
                     headers: headers_map,
-                    body: body_bytes,
+                    body,
                 })
             }
             // Anything else is an error
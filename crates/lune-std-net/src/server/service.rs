@@ -1,36 +1,62 @@
 use std::{future::Future, net::SocketAddr, pin::Pin, rc::Rc};
 
-use http_body_util::{BodyExt, Full};
-use hyper::{
-    body::{Bytes, Incoming},
-    service::Service,
-    Request, Response,
-};
+use http_body_util::Limited;
+use hyper::{body::Incoming, service::Service, Request, Response, StatusCode};
 use hyper_tungstenite::{is_upgrade_request, upgrade};
 
 use mlua::prelude::*;
 use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
 
 use super::{
-    super::websocket::NetWebSocket, keys::SvcKeys, request::LuaRequest, response::LuaResponse,
+    super::websocket::NetWebSocket,
+    keys::SvcKeys,
+    request::{collect_body, content_length, LuaRequest, LuaRequestBody, RequestBodyStream},
+    response::LuaResponse,
+    stream::ResponseBody,
 };
 
+const HANDLER_DRIVER_KEY: &str = "__net_serve_handler_driver";
+const HANDLER_DRIVER_SOURCE: &str = r"
+local handler, request, limitExceeded = ...
+local results = table.pack(pcall(handler, request))
+if not results[1] and not limitExceeded() then
+    error(results[2], 0)
+end
+return table.unpack(results, 1, results.n)
+";
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SvcOptions {
+    pub(super) max_body_size: Option<usize>,
+    pub(super) stream_body: bool,
+}
+
+fn payload_too_large() -> LuaResult<Response<ResponseBody>> {
+    Response::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .header("Content-Type", "text/plain")
+        .body(ResponseBody::from(b"Payload Too Large".to_vec()))
+        .into_lua_err()
+}
+
 #[derive(Debug, Clone)]
 pub(super) struct Svc {
     pub(super) lua: Rc<Lua>,
-    pub(super) addr: SocketAddr,
+    pub(super) remote_addr: SocketAddr,
     pub(super) keys: SvcKeys,
+    pub(super) options: SvcOptions,
 }
 
 impl Service<Request<Incoming>> for Svc {
-    type Response = Response<Full<Bytes>>;
+    type Response = Response<ResponseBody>;
     type Error = LuaError;
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
 
     fn call(&self, req: Request<Incoming>) -> Self::Future {
         let lua = self.lua.clone();
-        let addr = self.addr;
+        let remote_addr = self.remote_addr;
         let keys = self.keys;
+        let options = self.options;
 
         if keys.has_websocket_handler() && is_upgrade_request(&req) {
             Box::pin(async move {
@@ -50,7 +76,7 @@ impl Service<Request<Incoming>> for Svc {
                         .unwrap();
                 });
 
-                Ok(res)
+                Ok(res.map(ResponseBody::Full))
             })
         } else {
             let (head, body) = req.into_parts();
@@ -58,25 +84,76 @@ impl Service<Request<Incoming>> for Svc {
             Box::pin(async move {
                 let handler_request: LuaFunction = keys.request_handler(&lua).unwrap();
 
-                let body = body.collect().await.into_lua_err()?;
-                let body = body.to_bytes().to_vec();
+                // NOTE: Bodies that are declared to be too large are rejected right away,
+                // but clients may also omit the length, so we also limit bodies when reading
+                let max_size = options.max_body_size.unwrap_or(usize::MAX);
+                let declared_too_large = content_length(&head)
+                    .is_some_and(|len| usize::try_from(len).map_or(true, |len| len > max_size));
+                if declared_too_large {
+                    return payload_too_large();
+                }
+
+                let body = Limited::new(body, max_size);
+                let (body, limit_exceeded) = if options.stream_body {
+                    let stream = RequestBodyStream::new(body, max_size);
+                    let limit_exceeded = stream.limit_exceeded();
+                    (LuaRequestBody::Stream(stream), Some(limit_exceeded))
+                } else {
+                    match collect_body(body).await? {
+                        Some(bytes) => (LuaRequestBody::Bytes(bytes), None),
+                        None => return payload_too_large(),
+                    }
+                };
 
                 let lua_req = LuaRequest {
-                    _remote_addr: addr,
+                    remote_addr,
                     head,
                     body,
                 };
                 let lua_req_table = lua_req.into_lua_table(&lua)?;
 
-                let thread_id = lua.push_thread_back(handler_request, lua_req_table)?;
+                // NOTE: Streamed bodies may turn out to be too large while the handler
+                // is reading them, which errors in the handler, so we run it through
+                // a driver that lets us respond properly instead of dropping the connection
+                let thread_id = match limit_exceeded {
+                    None => lua.push_thread_back(handler_request, lua_req_table)?,
+                    Some(limit_exceeded) => {
+                        let driver = handler_driver(&lua)?;
+                        let limit_exceeded =
+                            lua.create_function(move |_, ()| Ok(limit_exceeded.get()))?;
+                        lua.push_thread_back(
+                            driver,
+                            (handler_request, lua_req_table, limit_exceeded),
+                        )?
+                    }
+                };
                 lua.track_thread(thread_id);
                 lua.wait_for_thread(thread_id).await;
-                let thread_res = lua
+                let mut thread_res = lua
                     .get_thread_result(thread_id)
                     .expect("Missing handler thread result")?;
 
-                LuaResponse::from_lua_multi(thread_res, &lua)?.into_response()
+                if options.stream_body {
+                    match thread_res.pop_front() {
+                        Some(LuaValue::Boolean(true)) => {}
+                        _ => return payload_too_large(),
+                    }
+                }
+
+                LuaResponse::from_lua_multi(thread_res, &lua)?.into_response(&lua)
             })
         }
     }
 }
+
+fn handler_driver(lua: &Lua) -> LuaResult<LuaFunction> {
+    if let Some(driver) = lua.named_registry_value(HANDLER_DRIVER_KEY)? {
+        return Ok(driver);
+    }
+    let driver = lua
+        .load(HANDLER_DRIVER_SOURCE)
+        .set_name("net.serve handler")
+        .into_function()?;
+    lua.set_named_registry_value(HANDLER_DRIVER_KEY, driver.clone())?;
+    Ok(driver)
+}
@@ -0,0 +1,386 @@
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bstr::{BString, ByteSlice};
+use http_body_util::Full;
+use hyper::body::{Body, Bytes, Frame, SizeHint};
+use tokio::{sync::mpsc, time::sleep};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
+
+#[cfg(feature = "fs")]
+use lune_std_fs::FsFile;
+use lune_utils::TableBuilder;
+
+const STREAM_CHANNEL_CAPACITY: usize = 8;
+#[cfg(feature = "fs")]
+const STREAM_FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+const STREAM_DRIVER_KEY: &str = "__net_serve_stream_driver";
+const STREAM_DRIVER_SOURCE: &str = r"
+local callback, writer = ...
+while not writer.closed do
+    local chunk = callback(writer)
+    if chunk == nil then
+        break
+    end
+    writer:write(chunk)
+end
+";
+
+type StreamSender = mpsc::Sender<LuaResult<Bytes>>;
+type StreamReceiver = mpsc::Receiver<LuaResult<Bytes>>;
+
+/**
+    The body of a response from `net.serve`, which is either
+    sent all at once, or streamed in chunks as they are produced.
+*/
+pub(super) enum ResponseBody {
+    Full(Full<Bytes>),
+    Stream(StreamReceiver),
+}
+
+impl From<Vec<u8>> for ResponseBody {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Full(Full::new(Bytes::from(bytes)))
+    }
+}
+
+impl Body for ResponseBody {
+    type Data = Bytes;
+    type Error = LuaError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match self.get_mut() {
+            Self::Full(full) => Pin::new(full)
+                .poll_frame(cx)
+                .map_err(|never| match never {}),
+            Self::Stream(rx) => rx
+                .poll_recv(cx)
+                .map(|chunk| chunk.map(|res| res.map(Frame::data))),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            Self::Full(full) => full.is_end_stream(),
+            Self::Stream(_) => false,
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self {
+            Self::Full(full) => full.size_hint(),
+            Self::Stream(_) => SizeHint::default(),
+        }
+    }
+}
+
+/**
+    A source for a streamed response body.
+*/
+#[derive(Debug)]
+pub(super) enum ResponseStreamSource {
+    Callback {
+        callback: LuaRegistryKey,
+        keep_alive: Option<Duration>,
+    },
+    #[cfg(feature = "fs")]
+    File(FsFile),
+}
+
+impl ResponseStreamSource {
+    /**
+        Starts streaming from this source in the background, returning the
+        body that will receive the streamed chunks as they are produced.
+    */
+    pub(super) fn spawn(self, lua: &Rc<Lua>) -> ResponseBody {
+        let (tx, rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+        match self {
+            Self::Callback {
+                callback,
+                keep_alive,
+            } => {
+                if let Some(interval) = keep_alive {
+                    spawn_keep_alive(lua, &tx, interval);
+                }
+                let lua_inner = Rc::clone(lua);
+                lua.spawn_local(async move {
+                    let writer = ResponseWriter::new(tx);
+                    if let Err(e) = run_callback(&lua_inner, &callback, writer.clone()).await {
+                        // NOTE: Sending an error aborts the response, letting
+                        // the client know that the body was not fully sent
+                        if let Some(tx) = writer.sender() {
+                            tx.send(Err(e)).await.ok();
+                        }
+                    }
+                    writer.close();
+                    lua_inner.remove_registry_value(callback).ok();
+                });
+            }
+            #[cfg(feature = "fs")]
+            Self::File(file) => {
+                lua.spawn_local(async move {
+                    loop {
+                        let chunk = match file.read(Some(STREAM_FILE_CHUNK_SIZE)).await {
+                            Ok(Some(chunk)) => Ok(Bytes::from(chunk)),
+                            Ok(None) => break,
+                            Err(e) => Err(e),
+                        };
+                        let failed = chunk.is_err();
+                        if tx.send(chunk).await.is_err() || failed {
+                            break;
+                        }
+                    }
+                    file.close().await.ok();
+                });
+            }
+        }
+        ResponseBody::Stream(rx)
+    }
+}
+
+async fn run_callback(
+    lua: &Lua,
+    callback: &LuaRegistryKey,
+    writer: ResponseWriter,
+) -> LuaResult<()> {
+    let driver = if let Some(driver) = lua.named_registry_value(STREAM_DRIVER_KEY)? {
+        driver
+    } else {
+        let driver = lua
+            .load(STREAM_DRIVER_SOURCE)
+            .set_name("net.serve stream")
+            .into_function()?;
+        lua.set_named_registry_value(STREAM_DRIVER_KEY, driver.clone())?;
+        driver
+    };
+    let callback: LuaFunction = lua.registry_value(callback)?;
+
+    let thread_id = lua.push_thread_back(driver, (callback, writer))?;
+    lua.track_thread(thread_id);
+    lua.wait_for_thread(thread_id).await;
+    lua.get_thread_result(thread_id)
+        .expect("Missing stream thread result")
+        .map(|_| ())
+}
+
+fn spawn_keep_alive(lua: &Lua, tx: &StreamSender, interval: Duration) {
+    // NOTE: We only hold a weak sender here, so that the body ends as
+    // soon as the stream callback is done, and is not kept open forever
+    let weak = tx.downgrade();
+    lua.spawn_local(async move {
+        loop {
+            sleep(interval).await;
+            let Some(tx) = weak.upgrade() else {
+                break;
+            };
+            if tx.send(Ok(Bytes::from_static(b":\n\n"))).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/**
+    A userdata for writing chunks to a streamed response body, which
+    also has helpers for writing events when using server-sent events.
+*/
+#[derive(Debug, Clone)]
+struct ResponseWriter {
+    tx: Rc<RefCell<Option<StreamSender>>>,
+}
+
+impl ResponseWriter {
+    fn new(tx: StreamSender) -> Self {
+        Self {
+            tx: Rc::new(RefCell::new(Some(tx))),
+        }
+    }
+
+    fn sender(&self) -> Option<StreamSender> {
+        self.tx.borrow().clone()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.tx
+            .borrow()
+            .as_ref()
+            .is_none_or(StreamSender::is_closed)
+    }
+
+    fn close(&self) {
+        self.tx.borrow_mut().take();
+    }
+
+    async fn write(&self, chunk: Vec<u8>) -> bool {
+        // NOTE: Writing an empty chunk would end the body early for some
+        // clients, so we skip those, but still report if we are closed
+        if chunk.is_empty() {
+            return !self.is_closed();
+        }
+        match self.sender() {
+            Some(tx) => tx.send(Ok(Bytes::from(chunk))).await.is_ok(),
+            None => false,
+        }
+    }
+}
+
+impl LuaUserData for ResponseWriter {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ResponseWriter");
+        fields.add_field_method_get("closed", |_, this| Ok(this.is_closed()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("write", |_, this, chunk: BString| async move {
+            Ok(this.write(chunk.into()).await)
+        });
+
+        methods.add_async_method("sendEvent", |_, this, event: LuaValue| async move {
+            let event = format_event(event)?;
+            Ok(this.write(event).await)
+        });
+
+        methods.add_async_method(
+            "sendComment",
+            |_, this, comment: Option<String>| async move {
+                let comment = format_comment(comment.as_deref().unwrap_or_default())?;
+                Ok(this.write(comment).await)
+            },
+        );
+    }
+}
+
+fn format_event(event: LuaValue) -> LuaResult<Vec<u8>> {
+    let (data, name, id, retry) = match event {
+        LuaValue::String(s) => (s.as_bytes().to_vec(), None, None, None),
+        LuaValue::Table(t) => {
+            let data: Option<BString> = t.get("data")?;
+            let name: Option<String> = t.get("event")?;
+            let id: Option<String> = t.get("id")?;
+            let retry: Option<u64> = t.get("retry")?;
+            (data.map(Into::into).unwrap_or_default(), name, id, retry)
+        }
+        value => {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ServerSentEvent",
+                message: Some(format!(
+                    "Invalid event - expected string or table, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    };
+
+    let mut formatted = Vec::new();
+    for (field, value) in [("id", id), ("event", name)] {
+        if let Some(value) = value {
+            if value.contains(['\r', '\n']) {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid event - '{field}' must not contain newlines"
+                )));
+            }
+            formatted.extend_from_slice(format!("{field}: {value}\n").as_bytes());
+        }
+    }
+    if let Some(retry) = retry {
+        formatted.extend_from_slice(format!("retry: {retry}\n").as_bytes());
+    }
+    for line in data.lines() {
+        formatted.extend_from_slice(b"data: ");
+        formatted.extend_from_slice(line);
+        formatted.push(b'\n');
+    }
+    if data.is_empty() || data.ends_with(b"\n") {
+        formatted.extend_from_slice(b"data: \n");
+    }
+    formatted.push(b'\n');
+    Ok(formatted)
+}
+
+fn format_comment(comment: &str) -> LuaResult<Vec<u8>> {
+    if comment.contains(['\r', '\n']) {
+        return Err(LuaError::runtime(
+            "Invalid comment - comments must not contain newlines",
+        ));
+    }
+    Ok(format!(":{comment}\n\n").into_bytes())
+}
+
+/**
+    A userdata wrapping a stream callback together with
+    its options, created using the `net.eventStream` function.
+*/
+#[derive(Debug)]
+pub(super) struct EventStream {
+    callback: LuaRegistryKey,
+    keep_alive: Option<Duration>,
+}
+
+impl EventStream {
+    pub(super) fn to_source(&self, lua: &Lua) -> LuaResult<ResponseStreamSource> {
+        // NOTE: The same event stream may be returned from more than one
+        // request, so every response needs its own key for the callback
+        let callback: LuaFunction = lua.registry_value(&self.callback)?;
+        Ok(ResponseStreamSource::Callback {
+            callback: lua.create_registry_value(callback)?,
+            keep_alive: self.keep_alive,
+        })
+    }
+}
+
+impl LuaUserData for EventStream {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "EventStream");
+    }
+}
+
+/**
+    Creates a response that streams server-sent events using the given callback.
+
+    Keep-alive comments are sent at the given interval for as long as the
+    stream is open, which prevents proxies from closing idle connections,
+    and lets writes fail quickly when the client has disconnected.
+*/
+pub fn create_event_stream<'lua>(
+    lua: &'lua Lua,
+    (callback, keep_alive): (LuaFunction<'lua>, Option<f64>),
+) -> LuaResult<LuaTable<'lua>> {
+    let keep_alive = match keep_alive {
+        None => None,
+        Some(secs) => Some(
+            Duration::try_from_secs_f64(secs)
+                .ok()
+                .filter(|d| !d.is_zero())
+                .ok_or_else(|| {
+                    LuaError::RuntimeError(format!(
+                "Invalid keep-alive interval - expected a positive number of seconds, got {secs}"
+            ))
+                })?,
+        ),
+    };
+    let stream = EventStream {
+        callback: lua.create_registry_value(callback)?,
+        keep_alive,
+    };
+    let headers = TableBuilder::new(lua)?
+        .with_value("Content-Type", "text/event-stream")?
+        .with_value("Cache-Control", "no-cache")?
+        .build()?;
+    TableBuilder::new(lua)?
+        .with_value("status", 200)?
+        .with_value("headers", headers)?
+        .with_value("body", stream)?
+        .build()
+}
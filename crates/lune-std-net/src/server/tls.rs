@@ -0,0 +1,188 @@
+use std::{
+    fs,
+    io::BufReader,
+    rc::Weak as WeakRc,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use tokio::time::sleep;
+use tokio_rustls::{
+    rustls::{
+        crypto::ring::sign::any_supported_type,
+        pki_types::{CertificateDer, PrivateKeyDer},
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        ServerConfig,
+    },
+    TlsAcceptor,
+};
+
+use crate::config::{ServeTlsConfig, TlsSource};
+
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/**
+    Creates a TLS acceptor for the given config.
+
+    The certificate and key are loaded right away, so that any
+    invalid config errors when starting the server, and are then
+    reloaded whenever their files on disk are modified.
+*/
+pub fn create_tls_acceptor(lua: &Lua, config: ServeTlsConfig) -> LuaResult<TlsAcceptor> {
+    let resolver = Arc::new(TlsCertResolver::new(config)?);
+    if resolver.has_files() {
+        spawn_reload_checks(lua, &resolver);
+    }
+
+    let mut server_config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/**
+    Periodically checks if the files for the certificate or key have been
+    modified, for as long as the resolver is in use by the server.
+
+    Checking and reading files is blocking, so it is done on a separate
+    thread, and never while resolving a certificate for a connection.
+*/
+fn spawn_reload_checks(lua: &Lua, resolver: &Arc<TlsCertResolver>) {
+    let lua_inner = lua
+        .app_data_ref::<WeakRc<Lua>>()
+        .expect("Missing weak lua ref")
+        .upgrade()
+        .expect("Lua was dropped unexpectedly");
+    let resolver: Weak<TlsCertResolver> = Arc::downgrade(resolver);
+    // NOTE: Checking for modifications should never keep the
+    // scheduler alive by itself, so we run in the background
+    lua.spawn_local_background(async move {
+        loop {
+            sleep(RELOAD_CHECK_INTERVAL).await;
+            let Some(resolver) = resolver.upgrade() else {
+                break;
+            };
+            lua_inner
+                .spawn_blocking(move || resolver.reload_if_modified())
+                .await;
+        }
+    });
+}
+
+/**
+    Resolves the certificate to use for incoming TLS connections,
+    which is reloaded from disk if its files have been modified.
+*/
+#[derive(Debug)]
+struct TlsCertResolver {
+    config: ServeTlsConfig,
+    key: RwLock<Arc<CertifiedKey>>,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl TlsCertResolver {
+    fn new(config: ServeTlsConfig) -> LuaResult<Self> {
+        let modified = modified_times(&config);
+        let key = load_certified_key(&config)?;
+        Ok(Self {
+            config,
+            key: RwLock::new(key),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    fn has_files(&self) -> bool {
+        self.config.cert.path().is_some() || self.config.key.path().is_some()
+    }
+
+    fn reload_if_modified(&self) {
+        let mut loaded_modified = self.modified.lock().expect("Poisoned certificate lock");
+        let modified = modified_times(&self.config);
+        if modified == *loaded_modified {
+            return;
+        }
+
+        // NOTE: Files may be modified separately or be only partially written,
+        // so we keep serving the old certificate until the new one is valid,
+        // and try to load it again during the next check if it was not
+        if let Ok(key) = load_certified_key(&self.config) {
+            *self.key.write().expect("Poisoned certificate lock") = key;
+            *loaded_modified = modified;
+        }
+    }
+}
+
+impl ResolvesServerCert for TlsCertResolver {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let key = self.key.read().expect("Poisoned certificate lock");
+        Some(Arc::clone(&key))
+    }
+}
+
+fn modified_times(config: &ServeTlsConfig) -> Vec<Option<SystemTime>> {
+    [&config.cert, &config.key]
+        .into_iter()
+        .map(|source| {
+            let path = source.path()?;
+            fs::metadata(path).and_then(|m| m.modified()).ok()
+        })
+        .collect()
+}
+
+fn read_source(source: &TlsSource, what: &str) -> LuaResult<Vec<u8>> {
+    source.read().map_err(|e| {
+        let origin = match source.path() {
+            Some(path) => format!(" from '{}'", path.display()),
+            None => String::new(),
+        };
+        LuaError::RuntimeError(format!("Failed to read TLS {what}{origin}\n{e}"))
+    })
+}
+
+fn load_certified_key(config: &ServeTlsConfig) -> LuaResult<Arc<CertifiedKey>> {
+    let cert_pem = read_source(&config.cert, "certificate")?;
+    let key_pem = read_source(&config.key, "private key")?;
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_pem.as_slice()))
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid TLS certificate\n{e}")))?
+        .into_iter()
+        .map(CertificateDer::from)
+        .collect::<Vec<_>>();
+    if certs.is_empty() {
+        return Err(LuaError::runtime(
+            "Invalid TLS certificate - no certificates were found",
+        ));
+    }
+
+    let key = load_private_key(&key_pem)?;
+    let signing_key = any_supported_type(&key)
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid TLS private key\n{e}")))?;
+
+    Ok(Arc::new(CertifiedKey::new(certs, signing_key)))
+}
+
+fn load_private_key(pem: &[u8]) -> LuaResult<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(pem);
+    loop {
+        let item = rustls_pemfile::read_one(&mut reader)
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid TLS private key\n{e}")))?;
+        match item {
+            Some(rustls_pemfile::Item::RSAKey(key)) => return Ok(PrivateKeyDer::Pkcs1(key.into())),
+            Some(rustls_pemfile::Item::PKCS8Key(key)) => {
+                return Ok(PrivateKeyDer::Pkcs8(key.into()))
+            }
+            Some(rustls_pemfile::Item::ECKey(key)) => return Ok(PrivateKeyDer::Sec1(key.into())),
+            Some(_) => {}
+            None => {
+                return Err(LuaError::runtime(
+                    "Invalid TLS private key - no private keys were found",
+                ))
+            }
+        }
+    }
+}
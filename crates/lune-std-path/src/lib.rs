@@ -0,0 +1,114 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR_STR};
+
+use mlua::prelude::*;
+
+use lune_utils::{
+    path::{clean_path, clean_path_and_make_absolute, diff_path},
+    TableBuilder,
+};
+
+/**
+    Creates the `path` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_value("separator", MAIN_SEPARATOR_STR)?
+        .with_function("join", path_join)?
+        .with_function("normalize", path_normalize)?
+        .with_function("relative", path_relative)?
+        .with_function("absolute", path_absolute)?
+        .with_function("parent", path_parent)?
+        .with_function("basename", path_basename)?
+        .with_function("stem", path_stem)?
+        .with_function("extension", path_extension)?
+        .with_function("withExtension", path_with_extension)?
+        .with_function("isAbsolute", path_is_absolute)?
+        .with_function("components", path_components)?
+        .build_readonly()
+}
+
+fn path_to_string(path: impl AsRef<Path>) -> String {
+    path.as_ref().to_string_lossy().to_string()
+}
+
+fn path_join(lua: &Lua, parts: LuaMultiValue) -> LuaResult<String> {
+    // NOTE: Joining works the same way as it does when resolving paths
+    // for require, meaning that any absolute part replaces the path so far
+    let mut joined = PathBuf::new();
+    for part in parts {
+        joined.push(String::from_lua(part, lua)?);
+    }
+    Ok(path_to_string(clean_path(joined)))
+}
+
+fn path_normalize(_: &Lua, path: String) -> LuaResult<String> {
+    Ok(path_to_string(clean_path(path)))
+}
+
+fn path_relative(_: &Lua, (path, base): (String, Option<String>)) -> LuaResult<String> {
+    let path = clean_path_and_make_absolute(path);
+    let base = clean_path_and_make_absolute(base.unwrap_or_else(|| String::from(".")));
+    match diff_path(&path, &base) {
+        Some(diff) if diff.as_os_str().is_empty() => Ok(String::from(".")),
+        Some(diff) => Ok(path_to_string(diff)),
+        None => Err(LuaError::RuntimeError(format!(
+            "Path '{}' can not be made relative to '{}'",
+            path.display(),
+            base.display()
+        ))),
+    }
+}
+
+fn path_absolute(_: &Lua, path: String) -> LuaResult<String> {
+    Ok(path_to_string(clean_path_and_make_absolute(path)))
+}
+
+fn path_parent(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    // Paths that consist of only a root, or a prefix and a root on
+    // Windows, such as `/` or `C:\`, do not have a parent directory
+    let path = clean_path(path);
+    let is_root = path
+        .components()
+        .all(|c| matches!(c, Component::Prefix(_) | Component::RootDir));
+    if is_root && path.has_root() {
+        return Ok(None);
+    }
+    Ok(Some(path_to_string(clean_path(path.join("..")))))
+}
+
+fn path_basename(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    Ok(Path::new(&path).file_name().map(path_to_string))
+}
+
+fn path_stem(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    Ok(Path::new(&path).file_stem().map(path_to_string))
+}
+
+fn path_extension(_: &Lua, path: String) -> LuaResult<Option<String>> {
+    Ok(Path::new(&path).extension().map(path_to_string))
+}
+
+fn path_with_extension(_: &Lua, (path, extension): (String, String)) -> LuaResult<String> {
+    let extension = extension.strip_prefix('.').unwrap_or(&extension);
+    Ok(path_to_string(Path::new(&path).with_extension(extension)))
+}
+
+fn path_is_absolute(_: &Lua, path: String) -> LuaResult<bool> {
+    Ok(Path::new(&path).is_absolute())
+}
+
+fn path_components(_: &Lua, path: String) -> LuaResult<Vec<String>> {
+    Ok(Path::new(&path)
+        .components()
+        .map(|component| match component {
+            Component::RootDir => MAIN_SEPARATOR_STR.to_string(),
+            component => path_to_string(component),
+        })
+        .collect())
+}
@@ -0,0 +1,183 @@
+use std::{io::Result as IoResult, process::ExitStatus, sync::Arc};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use tokio::{
+    process::Child,
+    sync::{mpsc, oneshot, watch},
+};
+
+use lune_utils::TableBuilder;
+
+use crate::{
+    pty::{PtyHandle, PtySize, PtyStream},
+    signal::{exit_status_signal, send_signal, signal_name, ProcessSignal},
+};
+
+mod reader;
+mod writer;
+
+pub use reader::ChildProcessReader;
+pub use writer::ChildProcessWriter;
+
+type KillRequest = (ProcessSignal, oneshot::Sender<IoResult<()>>);
+type ExitResult = Option<Result<ExitStatus, String>>;
+
+/**
+    A handle to a running child process.
+
+    The child itself is owned by a background task that waits for it to
+    exit, and any signals are sent through that same task, which makes
+    sure we never signal a process id that has already been reaped.
+*/
+#[derive(Clone)]
+pub struct ChildProcess {
+    pid: Option<u32>,
+    stdin: ChildProcessWriter,
+    stdout: ChildProcessReader,
+    stderr: ChildProcessReader,
+    kill_tx: mpsc::UnboundedSender<KillRequest>,
+    status_rx: watch::Receiver<ExitResult>,
+    pty: Option<Arc<PtyHandle>>,
+}
+
+impl ChildProcess {
+    pub fn new(lua: &Lua, mut child: Child) -> Self {
+        let stdin = ChildProcessWriter::new(child.stdin.take());
+        let stdout = ChildProcessReader::new(child.stdout.take());
+        let stderr = ChildProcessReader::new(child.stderr.take());
+        Self::from_parts(lua, child, stdin, stdout, stderr, None)
+    }
+
+    /**
+        Creates a handle to a child process that was spawned under a pseudo-terminal.
+
+        All output from the child is read from stdout, and stderr is always empty.
+    */
+    pub fn with_pty(lua: &Lua, child: Child, pty: PtyHandle) -> IoResult<Self> {
+        let stdin = ChildProcessWriter::new(Some(pty.stream()?));
+        let stdout = ChildProcessReader::new(Some(pty.stream()?));
+        let stderr = ChildProcessReader::new(None::<PtyStream>);
+        Ok(Self::from_parts(
+            lua,
+            child,
+            stdin,
+            stdout,
+            stderr,
+            Some(Arc::new(pty)),
+        ))
+    }
+
+    fn from_parts(
+        lua: &Lua,
+        child: Child,
+        stdin: ChildProcessWriter,
+        stdout: ChildProcessReader,
+        stderr: ChildProcessReader,
+        pty: Option<Arc<PtyHandle>>,
+    ) -> Self {
+        let pid = child.id();
+        let (kill_tx, kill_rx) = mpsc::unbounded_channel();
+        let (status_tx, status_rx) = watch::channel(None);
+        lua.spawn(drive_child(child, kill_rx, status_tx)).detach();
+
+        Self {
+            pid,
+            stdin,
+            stdout,
+            stderr,
+            kill_tx,
+            status_rx,
+            pty,
+        }
+    }
+
+    pub fn resize(&self, size: PtySize) -> LuaResult<()> {
+        match &self.pty {
+            Some(pty) => pty.resize(size).into_lua_err(),
+            None => Err(LuaError::runtime(
+                "Child process was not created with the 'pty' option",
+            )),
+        }
+    }
+
+    pub async fn kill(&self, signal: ProcessSignal) -> LuaResult<()> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if self.kill_tx.send((signal, reply_tx)).is_err() {
+            // The child has already exited, nothing to do
+            return Ok(());
+        }
+        match reply_rx.await {
+            Ok(res) => res.into_lua_err(),
+            Err(_) => Ok(()),
+        }
+    }
+
+    pub async fn status(&self) -> LuaResult<ExitStatus> {
+        let mut status_rx = self.status_rx.clone();
+        let result = status_rx
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| LuaError::runtime("Child process status is no longer available"))?
+            .clone();
+        match result {
+            Some(Ok(status)) => Ok(status),
+            Some(Err(e)) => Err(LuaError::runtime(e)),
+            None => unreachable!(),
+        }
+    }
+}
+
+async fn drive_child(
+    mut child: Child,
+    mut kill_rx: mpsc::UnboundedReceiver<KillRequest>,
+    status_tx: watch::Sender<ExitResult>,
+) {
+    loop {
+        tokio::select! {
+            res = child.wait() => {
+                status_tx.send_replace(Some(res.map_err(|e| e.to_string())));
+                break;
+            }
+            Some((signal, reply_tx)) = kill_rx.recv() => {
+                reply_tx.send(send_signal(&mut child, signal)).ok();
+            }
+        }
+    }
+}
+
+impl LuaUserData for ChildProcess {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ChildProcess");
+        fields.add_field_method_get("pid", |_, this| Ok(this.pid));
+        fields.add_field_method_get("stdin", |_, this| Ok(this.stdin.clone()));
+        fields.add_field_method_get("stdout", |_, this| Ok(this.stdout.clone()));
+        fields.add_field_method_get("stderr", |_, this| Ok(this.stderr.clone()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("kill", |_, this, signal: ProcessSignal| async move {
+            this.kill(signal).await
+        });
+        methods.add_method("resize", |_, this, size: PtySize| this.resize(size));
+        methods.add_async_method("status", |lua, this, (): ()| async move {
+            let status = this.status().await?;
+
+            /*
+                NOTE: An exit code may be missing if the process was
+                terminated by a signal, in which case it did not
+                exit successfully and we default to a code of 1
+            */
+            let code = status.code().unwrap_or(1);
+            let (signal, core_dumped) = exit_status_signal(status);
+
+            TableBuilder::new(lua)?
+                .with_value("ok", code == 0)?
+                .with_value("code", code)?
+                .with_value("signal", signal)?
+                .with_value("signalName", signal.and_then(signal_name))?
+                .with_value("coreDumped", core_dumped)?
+                .build_readonly()
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
+    sync::Mutex as AsyncMutex,
+};
+
+const DEFAULT_CHUNK_SIZE: usize = 8192;
+
+type BoxedReader = BufReader<Box<dyn AsyncRead + Send + Unpin>>;
+
+/**
+    A readable output stream of a child process, such as its stdout or stderr.
+
+    Reads are serialized, so reading concurrently from multiple
+    threads will never interleave bytes from different reads.
+*/
+#[derive(Clone)]
+pub struct ChildProcessReader {
+    inner: Arc<AsyncMutex<Option<BoxedReader>>>,
+}
+
+impl ChildProcessReader {
+    pub fn new<R>(reader: Option<R>) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let reader = reader.map(|r| {
+            let boxed: Box<dyn AsyncRead + Send + Unpin> = Box::new(r);
+            BufReader::new(boxed)
+        });
+        Self {
+            inner: Arc::new(AsyncMutex::new(reader)),
+        }
+    }
+
+    pub async fn read(&self, chunk_size: usize) -> LuaResult<Option<Vec<u8>>> {
+        let mut guard = self.inner.lock().await;
+        let Some(reader) = guard.as_mut() else {
+            return Ok(None);
+        };
+
+        let mut buffer = vec![0; chunk_size];
+        let len = reader.read(&mut buffer).await?;
+        if len == 0 {
+            return Ok(None);
+        }
+
+        buffer.truncate(len);
+        Ok(Some(buffer))
+    }
+
+    pub async fn read_line(&self) -> LuaResult<Option<Vec<u8>>> {
+        let mut guard = self.inner.lock().await;
+        let Some(reader) = guard.as_mut() else {
+            return Ok(None);
+        };
+
+        let mut buffer = Vec::new();
+        if reader.read_until(b'\n', &mut buffer).await? == 0 {
+            return Ok(None);
+        }
+
+        if buffer.ends_with(b"\n") {
+            buffer.pop();
+            if buffer.ends_with(b"\r") {
+                buffer.pop();
+            }
+        }
+        Ok(Some(buffer))
+    }
+
+    pub async fn read_to_end(&self) -> LuaResult<Vec<u8>> {
+        let mut guard = self.inner.lock().await;
+        let mut buffer = Vec::new();
+        if let Some(reader) = guard.as_mut() {
+            reader.read_to_end(&mut buffer).await?;
+        }
+        Ok(buffer)
+    }
+}
+
+impl LuaUserData for ChildProcessReader {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ChildProcessReader");
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("read", |lua, this, chunk_size: Option<usize>| async move {
+            let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+            if chunk_size == 0 {
+                return Err(LuaError::runtime("Chunk size must be greater than zero"));
+            }
+            match this.read(chunk_size).await? {
+                Some(bytes) => Ok(Some(lua.create_string(bytes)?)),
+                None => Ok(None),
+            }
+        });
+        methods.add_async_method("readLine", |lua, this, (): ()| async move {
+            match this.read_line().await? {
+                Some(bytes) => Ok(Some(lua.create_string(bytes)?)),
+                None => Ok(None),
+            }
+        });
+        methods.add_async_method("readToEnd", |lua, this, (): ()| async move {
+            lua.create_string(this.read_to_end().await?)
+        });
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::Mutex as AsyncMutex,
+};
+
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/**
+    A writable input stream of a child process, its stdin.
+
+    Once closed, the child process will see the end of its
+    input, and any further writes will throw an error.
+*/
+#[derive(Clone)]
+pub struct ChildProcessWriter {
+    inner: Arc<AsyncMutex<Option<BoxedWriter>>>,
+}
+
+impl ChildProcessWriter {
+    pub fn new<W>(writer: Option<W>) -> Self
+    where
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let writer = writer.map(|w| {
+            let boxed: BoxedWriter = Box::new(w);
+            boxed
+        });
+        Self {
+            inner: Arc::new(AsyncMutex::new(writer)),
+        }
+    }
+
+    pub async fn write(&self, bytes: &[u8]) -> LuaResult<()> {
+        let mut guard = self.inner.lock().await;
+        let Some(writer) = guard.as_mut() else {
+            return Err(LuaError::runtime("Stdin has been closed"));
+        };
+        writer.write_all(bytes).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    pub async fn close(&self) -> LuaResult<()> {
+        let mut guard = self.inner.lock().await;
+        if let Some(mut writer) = guard.take() {
+            writer.shutdown().await?;
+        }
+        Ok(())
+    }
+}
+
+impl LuaUserData for ChildProcessWriter {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ChildProcessWriter");
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("write", |_, this, data: LuaString| async move {
+            this.write(data.as_bytes()).await
+        });
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    io::{Error as IoError, Write},
+    process::Command,
+};
+
+/**
+    Replaces the current process with the given program, and only
+    returns if the program could not be started for some reason.
+
+    On Unix this uses `execvp`, meaning the program keeps the same process
+    id and receives any signals sent to it. On other platforms we emulate
+    this by running the program as a child process with inherited stdio,
+    and then exiting the current process with the same exit code.
+
+    The given `before_replace` function is called right before the current
+    process is replaced or exits, since nothing else gets to run after that.
+*/
+pub fn exec_replace(program: &str, args: &[String], before_replace: impl FnOnce()) -> IoError {
+    // NOTE: Any output that we have buffered would be lost when replacing
+    // the process, so we make sure that it has all been written first
+    std::io::stdout().flush().ok();
+    std::io::stderr().flush().ok();
+
+    let mut command = Command::new(program);
+    command.args(args);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        before_replace();
+        command.exec()
+    }
+
+    #[cfg(not(unix))]
+    {
+        match command.status() {
+            Ok(status) => {
+                before_replace();
+                std::process::exit(status.code().unwrap_or(1))
+            }
+            Err(e) => e,
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    env,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+static FALLBACK_START: OnceLock<Instant> = OnceLock::new();
+
+/**
+    Records the current time as the start time of the process, which is used to
+    compute the uptime of the process on platforms where it can not be read directly.
+*/
+pub fn init_start_time() {
+    FALLBACK_START.get_or_init(Instant::now);
+}
+
+/**
+    Gets the id of the parent process, if supported on the current platform.
+*/
+pub fn parent_id() -> Option<u32> {
+    #[cfg(unix)]
+    {
+        Some(std::os::unix::process::parent_id())
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/**
+    Gets the real user and group ids of the current process, if supported on the current platform.
+*/
+pub fn user_and_group_ids() -> (Option<u32>, Option<u32>) {
+    #[cfg(unix)]
+    {
+        // SAFETY: These functions are always successful and have no side effects
+        unsafe { (Some(libc::getuid()), Some(libc::getgid())) }
+    }
+    #[cfg(not(unix))]
+    {
+        (None, None)
+    }
+}
+
+/**
+    Gets the full path to the executable of the current process.
+*/
+pub fn executable_path() -> Option<String> {
+    env::current_exe()
+        .ok()
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/**
+    Gets the host name of the machine that the current process is running on.
+*/
+pub fn hostname() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buffer = [0u8; 256];
+        // SAFETY: The buffer is valid for writes of its full length
+        let res = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+        if res != 0 {
+            return None;
+        }
+        let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+        Some(String::from_utf8_lossy(&buffer[..len]).to_string())
+    }
+    #[cfg(not(unix))]
+    {
+        env::var("COMPUTERNAME").ok()
+    }
+}
+
+/**
+    Gets the amount of time that the current process has been running for.
+
+    On Linux this is read from procfs, and on other platforms it is
+    measured from the first time that the process module was created.
+*/
+pub fn uptime() -> Duration {
+    #[cfg(target_os = "linux")]
+    if let Some(uptime) = linux_uptime() {
+        return uptime;
+    }
+    FALLBACK_START.get_or_init(Instant::now).elapsed()
+}
+
+#[cfg(target_os = "linux")]
+fn linux_uptime() -> Option<Duration> {
+    // NOTE: The start time is the 22nd field of the stat file, given in clock
+    // ticks since boot - the command name in the second field may contain spaces,
+    // so we only start splitting fields after its closing parenthesis
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    let start_ticks: u64 = after_comm.split_whitespace().nth(19)?.parse().ok()?;
+
+    // SAFETY: sysconf has no side effects and is always safe to call
+    let ticks_per_sec = u64::try_from(unsafe { libc::sysconf(libc::_SC_CLK_TCK) }).ok()?;
+    if ticks_per_sec == 0 {
+        return None;
+    }
+
+    let system_uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let system_uptime: f64 = system_uptime.split_whitespace().next()?.parse().ok()?;
+
+    #[allow(clippy::cast_precision_loss)]
+    let start_secs = start_ticks as f64 / ticks_per_sec as f64;
+    Duration::try_from_secs_f64(system_uptime - start_secs).ok()
+}
@@ -5,26 +5,63 @@ use std::{
         self,
         consts::{ARCH, OS},
     },
+    future::Future,
     path::MAIN_SEPARATOR,
     process::Stdio,
+    rc::Rc,
+    time::Instant,
 };
 
 use mlua::prelude::*;
 
 use lune_utils::TableBuilder;
-use mlua_luau_scheduler::{Functions, LuaSpawnExt};
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, LuaSpawnExt, ThreadCancelGuard, ThreadId};
 use os_str_bytes::RawOsString;
-use tokio::io::AsyncWriteExt;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, watch},
+    task,
+};
 
+mod child;
+mod exec;
+mod info;
+mod on_signal;
 mod options;
-mod tee_writer;
+mod output;
+mod pipeline;
+mod pty;
+mod resource_usage;
+mod signal;
 mod wait_for_child;
+mod which;
 
-use self::options::ProcessSpawnOptions;
-use self::wait_for_child::{wait_for_child, WaitForChildResult};
+use self::child::ChildProcess;
+use self::exec::exec_replace;
+use self::on_signal::process_on_signal;
+use self::options::{ProcessPipelineStage, ProcessSpawnOptions, ProcessSpawnOptionsStdioKind};
+use self::output::{run_output_callbacks, ChildOutputs, OutputCallbackSender};
+use self::pipeline::run_pipeline;
+use self::pty::{PtyHandle, PtySize};
+use self::resource_usage::ResourceUsage;
+use self::signal::{exit_status_signal, signal_name};
+use self::wait_for_child::{
+    wait_for_child, WaitForChildLimits, WaitForChildOutputs, WaitForChildResult,
+};
+use self::which::find_executable;
 
 use lune_utils::path::get_current_dir;
 
+/**
+    A hook that is called right before `process.exec` replaces the current process.
+
+    Nothing else gets to run once the process has been replaced, including any
+    cleanup that normally happens when the Lua state is dropped, so this can be
+    set in the app data of the Lua state to run that cleanup before replacing.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessExecHook(pub fn(&Lua));
+
 /**
     Creates the `process` standard library module.
 
@@ -62,6 +99,9 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
                 .build_readonly()?,
         )?
         .build_readonly()?;
+    // Create constants for the ids of the current process
+    info::init_start_time();
+    let (uid, gid) = info::user_and_group_ids();
     // Create our process exit function, the scheduler crate provides this
     let fns = Functions::new(lua)?;
     let process_exit = fns.exit;
@@ -72,8 +112,21 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
         .with_value("args", args_tab)?
         .with_value("cwd", cwd_str)?
         .with_value("env", env_tab)?
+        .with_value("pid", std::process::id())?
+        .with_value("ppid", info::parent_id())?
+        .with_value("uid", uid)?
+        .with_value("gid", gid)?
+        .with_value("exePath", info::executable_path())?
         .with_value("exit", process_exit)?
+        .with_function("hostname", |_, (): ()| Ok(info::hostname()))?
+        .with_function("uptime", |_, (): ()| Ok(info::uptime().as_secs_f64()))?
+        .with_function("resourceUsage", |_, (): ()| Ok(ResourceUsage::current()))?
+        .with_function("onSignal", process_on_signal)?
+        .with_function("which", process_which)?
+        .with_function("exec", process_exec)?
         .with_async_function("spawn", process_spawn)?
+        .with_async_function("pipeline", process_pipeline)?
+        .with_function("create", process_create)?
         .build_readonly()
 }
 
@@ -141,42 +194,208 @@ fn process_env_iter<'lua>(
     })
 }
 
+fn process_which(_: &Lua, name: String) -> LuaResult<Option<String>> {
+    Ok(find_executable(&name).map(|path| path.to_string_lossy().to_string()))
+}
+
+fn process_exec(lua: &Lua, (program, args): (String, Option<Vec<String>>)) -> LuaResult<()> {
+    // NOTE: Cleaning up can not be undone, so we make sure that the program exists
+    // before doing that, meaning that starting it should only fail in very rare cases
+    if find_executable(&program).is_none() {
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to execute '{program}'\nProgram could not be found"
+        )));
+    }
+    let hook = lua.app_data_ref::<ProcessExecHook>().map(|hook| *hook);
+    let e = exec_replace(&program, &args.unwrap_or_default(), || {
+        if let Some(ProcessExecHook(hook)) = hook {
+            hook(lua);
+        }
+    });
+    Err(LuaError::RuntimeError(format!(
+        "Failed to execute '{program}'\n{e}"
+    )))
+}
+
 async fn process_spawn(
     lua: &Lua,
     (program, args, options): (String, Option<Vec<String>>, ProcessSpawnOptions),
 ) -> LuaResult<LuaTable> {
-    let res = lua.spawn(spawn_command(program, args, options)).await?;
+    let (_cancel_guard, cancel_tx) = listen_for_cancel(lua);
+    let (callback_tx, callback_rx) = mpsc::unbounded_channel();
+    let task = lua.spawn(spawn_command(
+        program,
+        args,
+        options,
+        callback_tx,
+        cancelled(cancel_tx.subscribe()),
+    ));
+
+    // NOTE: We must always wait for the child to exit, even
+    // if a callback errored and the child is being cancelled
+    let callbacks = run_output_callbacks(lua, callback_rx, &cancel_tx).await;
+    let res = task.await;
+    callbacks?;
+
+    create_spawn_result(lua, &res?)
+}
+
+async fn process_pipeline(lua: &Lua, stages: Vec<ProcessPipelineStage>) -> LuaResult<LuaTable> {
+    if stages.is_empty() {
+        return Err(LuaError::runtime("Pipeline must have at least one stage"));
+    }
+    for (index, stage) in stages.iter().enumerate() {
+        stage.validate(index, stages.len())?;
+    }
+
+    let (_cancel_guard, cancel_tx) = listen_for_cancel(lua);
+    let (callback_tx, callback_rx) = mpsc::unbounded_channel();
+    let cancel_rx = cancel_tx.subscribe();
+    let task = run_pipeline(lua, stages, callback_tx, move || {
+        cancelled(cancel_rx.clone())
+    })?;
+
+    let callbacks = run_output_callbacks(lua, callback_rx, &cancel_tx).await;
+    let results = task.await;
+    callbacks?;
+    let results = results?;
+
+    let last = results.last().expect("pipeline has at least one stage");
+    let stage_results = results
+        .iter()
+        .map(|res| create_spawn_result(lua, res))
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    // NOTE: Like with "set -o pipefail" in a shell, the
+    // pipeline is only ok if every single stage was ok
+    TableBuilder::new(lua)?
+        .with_value("ok", results.iter().all(spawn_result_ok))?
+        .with_value("code", spawn_result_code(last))?
+        .with_value("stdout", lua.create_string(&last.stdout)?)?
+        .with_value("stderr", lua.create_string(&last.stderr)?)?
+        .with_value(
+            "stages",
+            TableBuilder::new(lua)?
+                .with_sequential_values(stage_results)?
+                .build_readonly()?,
+        )?
+        .build_readonly()
+}
+
+/**
+    Listens for cancellation of the current thread.
+
+    The future for an async function is not dropped when the calling thread
+    gets cancelled, only once the thread is garbage collected, so we need to
+    listen for cancellation to be able to terminate any children right away.
+*/
+fn listen_for_cancel(lua: &Lua) -> (ThreadCancelGuard, Rc<watch::Sender<bool>>) {
+    let cancel_tx = Rc::new(watch::Sender::new(false));
+    let thread_id = ThreadId::from(&lua.current_thread());
+    let cancel_tx_inner = Rc::clone(&cancel_tx);
+    let guard = lua.on_thread_cancel(thread_id, move || {
+        cancel_tx_inner.send_replace(true);
+    });
+    (guard, cancel_tx)
+}
+
+async fn cancelled(mut cancel_rx: watch::Receiver<bool>) {
+    // NOTE: An error here means the sender was dropped without
+    // cancelling, and we should then never cancel the child
+    if cancel_rx.wait_for(|cancelled| *cancelled).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
 
+fn spawn_result_code(res: &WaitForChildResult) -> i32 {
     /*
         NOTE: If an exit code was not given by the child process,
-        we default to 1 if it yielded any error output, otherwise 0
+        we default to 1 if it yielded any error output or timed out,
+        otherwise 0
 
         An exit code may be missing if the process was terminated by
         some external signal, which is the only time we use this default
     */
-    let code = res
-        .status
+    res.status
         .code()
-        .unwrap_or(i32::from(!res.stderr.is_empty()));
+        .unwrap_or(i32::from(res.timed_out || !res.stderr.is_empty()))
+}
+
+fn spawn_result_ok(res: &WaitForChildResult) -> bool {
+    spawn_result_code(res) == 0 && !res.timed_out
+}
+
+fn create_spawn_result<'lua>(
+    lua: &'lua Lua,
+    res: &WaitForChildResult,
+) -> LuaResult<LuaTable<'lua>> {
+    let (signal, core_dumped) = exit_status_signal(res.status);
 
     // Construct and return a readonly lua table with results
     TableBuilder::new(lua)?
-        .with_value("ok", code == 0)?
-        .with_value("code", code)?
+        .with_value("ok", spawn_result_ok(res))?
+        .with_value("code", spawn_result_code(res))?
         .with_value("stdout", lua.create_string(&res.stdout)?)?
         .with_value("stderr", lua.create_string(&res.stderr)?)?
+        .with_value(
+            "output",
+            res.output
+                .as_ref()
+                .map(|output| lua.create_string(output))
+                .transpose()?,
+        )?
+        .with_value("timedOut", res.timed_out)?
+        .with_value("signal", signal)?
+        .with_value("signalName", signal.and_then(signal_name))?
+        .with_value("coreDumped", core_dumped)?
+        .with_value("pid", res.pid)?
+        .with_value("duration", res.duration.as_secs_f64())?
+        .with_value("resourceUsage", res.resource_usage)?
         .build_readonly()
 }
 
+fn process_create(
+    lua: &Lua,
+    (program, args, options): (String, Option<Vec<String>>, ProcessSpawnOptions),
+) -> LuaResult<ChildProcess> {
+    if let Some(size) = options.pty {
+        let (child, pty) = PtyHandle::spawn(options.into_command(program, args), size)?;
+        return Ok(ChildProcess::with_pty(lua, child, pty)?);
+    }
+
+    // NOTE: All streams are piped here, since the whole point of creating
+    // a child process is to interact with it while it is running
+    let child = options
+        .into_command(program, args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    Ok(ChildProcess::new(lua, child))
+}
+
 async fn spawn_command(
     program: String,
     args: Option<Vec<String>>,
     mut options: ProcessSpawnOptions,
+    callback_tx: OutputCallbackSender,
+    cancelled: impl Future<Output = ()>,
 ) -> LuaResult<WaitForChildResult> {
-    let stdout = options.stdio.stdout;
-    let stderr = options.stdio.stderr;
+    if let Some(size) = options.pty {
+        return spawn_command_pty(program, args, options, size, callback_tx, cancelled).await;
+    }
+
+    let stdout_kind = std::mem::take(&mut options.stdio.stdout);
+    let stderr_kind = std::mem::take(&mut options.stdio.stderr);
     let stdin = options.stdio.stdin.take();
+    let child_outputs = ChildOutputs::new(&stdout_kind, &stderr_kind).into_lua_err()?;
+    let limits = WaitForChildLimits {
+        timeout: options.timeout,
+        kill_grace_period: options.kill_grace_period,
+    };
 
+    let started = Instant::now();
     let mut child = options
         .into_command(program, args)
         .stdin(if stdin.is_some() {
@@ -184,8 +403,9 @@ async fn spawn_command(
         } else {
             Stdio::null()
         })
-        .stdout(stdout.as_stdio())
-        .stderr(stderr.as_stdio())
+        .stdout(child_outputs.stdout)
+        .stderr(child_outputs.stderr)
+        .kill_on_drop(true)
         .spawn()?;
 
     if let Some(stdin) = stdin {
@@ -193,5 +413,75 @@ async fn spawn_command(
         child_stdin.write_all(&stdin).await.into_lua_err()?;
     }
 
-    wait_for_child(child, stdout, stderr).await
+    let outputs = WaitForChildOutputs {
+        stdout_kind,
+        stderr_kind,
+        merged: child_outputs.merged,
+        pty: None,
+        callback_tx,
+    };
+    wait_for_child(child, outputs, limits, started, cancelled).await
+}
+
+/**
+    Spawns a command under a new pseudo-terminal, which the child sees for all of
+    its stdio - everything it outputs is read as stdout, and stderr is always empty.
+*/
+async fn spawn_command_pty(
+    program: String,
+    args: Option<Vec<String>>,
+    mut options: ProcessSpawnOptions,
+    size: PtySize,
+    callback_tx: OutputCallbackSender,
+    cancelled: impl Future<Output = ()>,
+) -> LuaResult<WaitForChildResult> {
+    let stdout_kind = std::mem::take(&mut options.stdio.stdout);
+    if !matches!(
+        stdout_kind,
+        ProcessSpawnOptionsStdioKind::Default
+            | ProcessSpawnOptionsStdioKind::Inherit
+            | ProcessSpawnOptionsStdioKind::Callback { .. }
+    ) {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid stdio kind '{stdout_kind}' - only 'default', 'inherit' \
+            and 'callback' can be used together with the 'pty' option"
+        )));
+    }
+    let stdin = options.stdio.stdin.take();
+    let limits = WaitForChildLimits {
+        timeout: options.timeout,
+        kill_grace_period: options.kill_grace_period,
+    };
+
+    let started = Instant::now();
+    let mut command = options.into_command(program, args);
+    command.kill_on_drop(true);
+    let (child, pty) = PtyHandle::spawn(command, size)?;
+
+    // NOTE: Terminals echo input back as output, so input must be written while
+    // the output is being read, otherwise the terminal may fill up and block
+    let stdin_task = match stdin {
+        Some(stdin) => {
+            let mut pty_stdin = pty.stream()?;
+            Some(task::spawn(async move {
+                pty_stdin.write_all(&stdin).await.ok();
+            }))
+        }
+        None => None,
+    };
+
+    let outputs = WaitForChildOutputs {
+        stdout_kind,
+        stderr_kind: ProcessSpawnOptionsStdioKind::None,
+        merged: None,
+        pty: Some(pty.stream()?),
+        callback_tx,
+    };
+    let res = wait_for_child(child, outputs, limits, started, cancelled).await;
+
+    if let Some(stdin_task) = stdin_task {
+        stdin_task.abort();
+    }
+
+    res
 }
@@ -0,0 +1,179 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+    process::ExitCode,
+    rc::{Rc, Weak},
+};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
+
+use crate::signal::ProcessSignal;
+
+/**
+    Handlers connected using `process.onSignal`, stored in Lua app data.
+
+    While at least one handler is connected for a signal, the default action of
+    terminating the process is suppressed, and it is then up to the handlers to
+    exit the process using `process.exit` if they want to.
+*/
+#[derive(Default)]
+struct SignalHandlers {
+    next_id: Cell<usize>,
+    handlers: RefCell<HashMap<ProcessSignal, Vec<(usize, LuaRegistryKey)>>>,
+    listening: RefCell<HashSet<ProcessSignal>>,
+}
+
+impl SignalHandlers {
+    fn get_or_create(lua: &Lua) -> Rc<Self> {
+        if let Some(handlers) = lua.app_data_ref::<Rc<Self>>() {
+            return Rc::clone(&handlers);
+        }
+        let handlers = Rc::new(Self::default());
+        lua.set_app_data(Rc::clone(&handlers));
+        handlers
+    }
+
+    fn insert(&self, signal: ProcessSignal, key: LuaRegistryKey) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+        self.handlers
+            .borrow_mut()
+            .entry(signal)
+            .or_default()
+            .push((id, key));
+        id
+    }
+
+    fn remove(&self, signal: ProcessSignal, id: usize) -> Option<LuaRegistryKey> {
+        let mut handlers = self.handlers.borrow_mut();
+        let signal_handlers = handlers.get_mut(&signal)?;
+        let index = signal_handlers.iter().position(|(i, _)| *i == id)?;
+        Some(signal_handlers.remove(index).1)
+    }
+
+    fn functions<'lua>(
+        &self,
+        lua: &'lua Lua,
+        signal: ProcessSignal,
+    ) -> LuaResult<Vec<LuaFunction<'lua>>> {
+        let handlers = self.handlers.borrow();
+        handlers
+            .get(&signal)
+            .into_iter()
+            .flatten()
+            .map(|(_, key)| lua.registry_value(key))
+            .collect()
+    }
+}
+
+/**
+    Marks a signal as being listened for, until dropped.
+
+    The listener is dropped along with the scheduler it was spawned on,
+    so this lets us start listening again if the scheduler is restarted.
+*/
+struct ListeningGuard {
+    handlers: Rc<SignalHandlers>,
+    signal: ProcessSignal,
+}
+
+impl Drop for ListeningGuard {
+    fn drop(&mut self) {
+        self.handlers.listening.borrow_mut().remove(&self.signal);
+    }
+}
+
+#[cfg(unix)]
+fn listen(signal: ProcessSignal) -> IoResult<tokio::signal::unix::Signal> {
+    use tokio::signal::unix::{signal as unix_signal, SignalKind};
+    if signal == ProcessSignal::Kill {
+        return Err(IoError::new(
+            IoErrorKind::Unsupported,
+            format!("{signal} can not be handled"),
+        ));
+    }
+    unix_signal(SignalKind::from_raw(signal.as_raw()))
+}
+
+#[cfg(windows)]
+fn listen(signal: ProcessSignal) -> IoResult<tokio::signal::windows::CtrlC> {
+    if signal != ProcessSignal::Interrupt {
+        return Err(IoError::new(
+            IoErrorKind::Unsupported,
+            format!("{signal} can not be handled on this platform"),
+        ));
+    }
+    tokio::signal::windows::ctrl_c()
+}
+
+/**
+    Runs all handlers connected for the given signal as new threads on the scheduler,
+    or exits the process if there are no handlers connected, which emulates the default
+    action for the signal - we can't restore it since the signal is now being listened for.
+*/
+fn deliver(lua: &Lua, handlers: &SignalHandlers, signal: ProcessSignal) -> LuaResult<()> {
+    let functions = handlers.functions(lua, signal)?;
+    if functions.is_empty() {
+        lua.set_exit_code(ExitCode::from(signal.exit_code()));
+        return Ok(());
+    }
+    for function in functions {
+        lua.push_thread_front(function, signal.to_string())?;
+    }
+    Ok(())
+}
+
+pub fn process_on_signal<'lua>(
+    lua: &'lua Lua,
+    (signal, handler): (String, LuaFunction<'lua>),
+) -> LuaResult<LuaFunction<'lua>> {
+    let signal: ProcessSignal = signal.parse()?;
+    let handlers = SignalHandlers::get_or_create(lua);
+
+    let should_listen = handlers.listening.borrow_mut().insert(signal);
+    if should_listen {
+        let mut stream = match listen(signal) {
+            Ok(stream) => stream,
+            Err(e) => {
+                handlers.listening.borrow_mut().remove(&signal);
+                return Err(LuaError::RuntimeError(format!(
+                    "Failed to listen for signal {signal}\n{e}"
+                )));
+            }
+        };
+        let lua_inner = lua
+            .app_data_ref::<Weak<Lua>>()
+            .expect("Missing weak lua ref")
+            .upgrade()
+            .expect("Lua was dropped unexpectedly");
+        let guard = ListeningGuard {
+            handlers: Rc::clone(&handlers),
+            signal,
+        };
+        // NOTE: Listening for signals should never keep the
+        // scheduler alive by itself, so we run in the background
+        lua.spawn_local_background(async move {
+            let guard = guard;
+            while stream.recv().await.is_some() {
+                if let Err(e) = deliver(&lua_inner, &guard.handlers, signal) {
+                    lua_inner.report_error(&LuaError::RuntimeError(format!(
+                        "Failed to handle signal {signal}\n{e}"
+                    )));
+                }
+            }
+        });
+    }
+
+    let key = lua.create_registry_value(handler)?;
+    let id = handlers.insert(signal, key);
+
+    let handlers = Rc::downgrade(&handlers);
+    lua.create_function(move |lua, (): ()| {
+        if let Some(key) = handlers.upgrade().and_then(|h| h.remove(signal, id)) {
+            lua.remove_registry_value(key)?;
+        }
+        Ok(())
+    })
+}
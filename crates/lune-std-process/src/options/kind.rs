@@ -1,8 +1,11 @@
-use std::{fmt, process::Stdio, str::FromStr};
+use std::{
+    fmt, fs::OpenOptions, io::Result as IoResult, path::PathBuf, process::Stdio, str::FromStr,
+    sync::Arc,
+};
 
 use mlua::prelude::*;
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum ProcessSpawnOptionsStdioKind {
     // TODO: We need better more obvious names
     // for these, but that is a breaking change
@@ -11,18 +14,93 @@ pub enum ProcessSpawnOptionsStdioKind {
     Forward,
     Inherit,
     None,
+    Merged,
+    File {
+        path: PathBuf,
+        append: bool,
+    },
+    Callback {
+        callback: Arc<LuaRegistryKey>,
+        lines: bool,
+    },
 }
 
 impl ProcessSpawnOptionsStdioKind {
-    pub fn all() -> &'static [Self] {
-        &[Self::Default, Self::Forward, Self::Inherit, Self::None]
+    pub fn all_names() -> &'static [&'static str] {
+        &[
+            "default", "forward", "inherit", "none", "merged", "file", "callback",
+        ]
     }
 
-    pub fn as_stdio(self) -> Stdio {
-        match self {
+    /**
+        Creates the stdio to give to the child process for this kind.
+
+        Note that this will open the file for the `File` kind, and that
+        the `Merged` kind must be handled separately, since it needs an
+        output pipe that is shared between both stdout and stderr.
+    */
+    pub fn as_stdio(&self) -> IoResult<Stdio> {
+        Ok(match self {
             Self::None => Stdio::null(),
             Self::Forward => Stdio::inherit(),
+            Self::File { path, append } => OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(*append)
+                .truncate(!*append)
+                .open(path)?
+                .into(),
             _ => Stdio::piped(),
+        })
+    }
+
+    fn from_table(lua: &Lua, table: &LuaTable) -> LuaResult<Self> {
+        let kind: String = table.get("kind").map_err(|_| {
+            LuaError::runtime("Invalid spawn options stdio kind - missing string field 'kind'")
+        })?;
+        let kind = kind.trim().to_ascii_lowercase();
+        match kind.as_str() {
+            "file" => {
+                let path: String = table.get("path").map_err(|_| {
+                    LuaError::runtime("Invalid stdio kind 'file' - missing string field 'path'")
+                })?;
+                let append = match table.get::<_, Option<String>>("mode")?.as_deref() {
+                    None | Some("truncate") => false,
+                    Some("append") => true,
+                    Some(mode) => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid stdio kind 'file' - got mode '{mode}', \
+                            expected one of 'truncate', 'append'"
+                        )))
+                    }
+                };
+                Ok(Self::File {
+                    path: PathBuf::from(path),
+                    append,
+                })
+            }
+            "callback" => {
+                let callback: LuaFunction = table.get("callback").map_err(|_| {
+                    LuaError::runtime(
+                        "Invalid stdio kind 'callback' - missing function field 'callback'",
+                    )
+                })?;
+                let lines = match table.get::<_, Option<String>>("mode")?.as_deref() {
+                    None | Some("chunk") => false,
+                    Some("line") => true,
+                    Some(mode) => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid stdio kind 'callback' - got mode '{mode}', \
+                            expected one of 'chunk', 'line'"
+                        )))
+                    }
+                };
+                Ok(Self::Callback {
+                    callback: Arc::new(lua.create_registry_value(callback)?),
+                    lines,
+                })
+            }
+            _ => kind.parse(),
         }
     }
 }
@@ -34,6 +112,9 @@ impl fmt::Display for ProcessSpawnOptionsStdioKind {
             Self::Forward => "forward",
             Self::Inherit => "inherit",
             Self::None => "none",
+            Self::Merged => "merged",
+            Self::File { .. } => "file",
+            Self::Callback { .. } => "callback",
         };
         f.write_str(s)
     }
@@ -47,11 +128,18 @@ impl FromStr for ProcessSpawnOptionsStdioKind {
             "forward" => Self::Forward,
             "inherit" => Self::Inherit,
             "none" => Self::None,
+            "merged" => Self::Merged,
+            "file" | "callback" => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid spawn options stdio kind - '{}' must be given as a table",
+                    s.trim()
+                )))
+            }
             _ => {
                 return Err(LuaError::RuntimeError(format!(
                     "Invalid spawn options stdio kind - got '{}', expected one of {}",
                     s,
-                    ProcessSpawnOptionsStdioKind::all()
+                    ProcessSpawnOptionsStdioKind::all_names()
                         .iter()
                         .map(|k| format!("'{k}'"))
                         .collect::<Vec<_>>()
@@ -63,15 +151,20 @@ impl FromStr for ProcessSpawnOptionsStdioKind {
 }
 
 impl<'lua> FromLua<'lua> for ProcessSpawnOptionsStdioKind {
-    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         match value {
             LuaValue::Nil => Ok(Self::default()),
             LuaValue::String(s) => s.to_str()?.parse(),
+            LuaValue::Table(t) => Self::from_table(lua, &t),
+            LuaValue::Function(f) => Ok(Self::Callback {
+                callback: Arc::new(lua.create_registry_value(f)?),
+                lines: false,
+            }),
             _ => Err(LuaError::FromLuaConversionError {
                 from: value.type_name(),
                 to: "ProcessSpawnOptionsStdioKind",
                 message: Some(format!(
-                    "Invalid spawn options stdio kind - expected string, got {}",
+                    "Invalid spawn options stdio kind - expected string, table or function, got {}",
                     value.type_name()
                 )),
             }),
@@ -2,24 +2,38 @@ use std::{
     collections::HashMap,
     env::{self},
     path::PathBuf,
+    time::Duration,
 };
 
 use directories::UserDirs;
 use mlua::prelude::*;
 use tokio::process::Command;
 
+use crate::pty::PtySize;
+
 mod kind;
+mod stage;
 mod stdio;
 
 pub(super) use kind::*;
+pub(super) use stage::*;
 pub(super) use stdio::*;
 
 #[derive(Debug, Clone, Default)]
 pub(super) struct ProcessSpawnOptions {
     pub cwd: Option<PathBuf>,
     pub envs: HashMap<String, String>,
+    pub clear_env: bool,
+    pub inherit_env: Option<Vec<String>>,
+    pub remove_env: Vec<String>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub umask: Option<u32>,
+    pub pty: Option<PtySize>,
     pub shell: Option<String>,
     pub stdio: ProcessSpawnOptionsStdio,
+    pub timeout: Option<Duration>,
+    pub kill_grace_period: Option<Duration>,
 }
 
 impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
@@ -92,6 +106,29 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
             }
         }
 
+        /*
+            If we got options for which variables to inherit from our own
+            environment, make sure those are valid - note that giving an
+            explicit list of variables to inherit also clears everything else
+        */
+        this.clear_env = parse_bool(&value, "clearEnv")?;
+        this.inherit_env = parse_string_list(&value, "inheritEnv")?;
+        this.remove_env = parse_string_list(&value, "removeEnv")?.unwrap_or_default();
+
+        /*
+            If we got a user, group, or umask to run as, make sure
+            those are valid, and that we are on a supported platform
+        */
+        this.uid = parse_unix_id(&value, "uid")?;
+        this.gid = parse_unix_id(&value, "gid")?;
+        this.umask = parse_umask(&value)?;
+
+        /*
+            If we should run under a pseudo-terminal, also
+            get the size of the terminal to use, if given
+        */
+        this.pty = parse_pty(&value)?;
+
         /*
             If we got a shell to use:
 
@@ -133,6 +170,14 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
             }
         }
 
+        /*
+            If we got a timeout, and optionally a grace period to wait
+            for after asking the child to terminate, make sure they are
+            valid non-negative durations given in seconds
+        */
+        this.timeout = parse_duration(&value, "timeout")?;
+        this.kill_grace_period = parse_duration(&value, "killGracePeriod")?;
+
         Ok(this)
     }
 }
@@ -168,10 +213,168 @@ impl ProcessSpawnOptions {
         if let Some(cwd) = self.cwd {
             cmd.current_dir(cwd);
         }
+        if self.clear_env || self.inherit_env.is_some() {
+            cmd.env_clear();
+        }
+        for key in self.inherit_env.into_iter().flatten() {
+            if let Some(value) = env::var_os(&key) {
+                cmd.env(key, value);
+            }
+        }
+        for key in self.remove_env {
+            cmd.env_remove(key);
+        }
         if !self.envs.is_empty() {
             cmd.envs(self.envs);
         }
 
+        // Set user, group and umask to run as
+        #[cfg(unix)]
+        {
+            if let Some(uid) = self.uid {
+                cmd.uid(uid);
+            }
+            if let Some(gid) = self.gid {
+                cmd.gid(gid);
+            }
+            if let Some(umask) = self.umask {
+                // SAFETY: The closure runs in the forked child before exec, and
+                // only calls umask, which is async-signal-safe and can not fail
+                unsafe {
+                    cmd.pre_exec(move || {
+                        libc::umask(umask as libc::mode_t);
+                        Ok(())
+                    });
+                }
+            }
+        }
+
         cmd
     }
 }
+
+fn parse_duration(value: &LuaTable, key: &'static str) -> LuaResult<Option<Duration>> {
+    let secs = match value.get(key)? {
+        LuaValue::Nil => return Ok(None),
+        LuaValue::Integer(i) => i as f64,
+        LuaValue::Number(n) => n,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid type for option '{key}' - expected 'number', got '{}'",
+                value.type_name()
+            )))
+        }
+    };
+    match Duration::try_from_secs_f64(secs) {
+        Ok(duration) => Ok(Some(duration)),
+        Err(_) => Err(LuaError::RuntimeError(format!(
+            "Invalid value for option '{key}' - expected a non-negative number of seconds, got '{secs}'"
+        ))),
+    }
+}
+
+fn parse_bool(value: &LuaTable, key: &'static str) -> LuaResult<bool> {
+    match value.get(key)? {
+        LuaValue::Nil => Ok(false),
+        LuaValue::Boolean(b) => Ok(b),
+        value => Err(LuaError::RuntimeError(format!(
+            "Invalid type for option '{key}' - expected 'boolean', got '{}'",
+            value.type_name()
+        ))),
+    }
+}
+
+fn parse_pty(value: &LuaTable) -> LuaResult<Option<PtySize>> {
+    let size: Option<PtySize> = value.get("ptySize")?;
+    if parse_bool(value, "pty")? {
+        Ok(Some(size.unwrap_or_default()))
+    } else if size.is_some() {
+        Err(LuaError::runtime(
+            "Invalid option 'ptySize' - option 'pty' must also be set to true",
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_string_list(value: &LuaTable, key: &'static str) -> LuaResult<Option<Vec<String>>> {
+    match value.get(key)? {
+        LuaValue::Nil => Ok(None),
+        LuaValue::Table(t) => {
+            let list = t
+                .sequence_values::<String>()
+                .collect::<LuaResult<Vec<_>>>()
+                .with_context(|_| {
+                    format!("Invalid value for option '{key}' - expected a list of strings")
+                })?;
+            Ok(Some(list))
+        }
+        value => Err(LuaError::RuntimeError(format!(
+            "Invalid type for option '{key}' - expected 'table', got '{}'",
+            value.type_name()
+        ))),
+    }
+}
+
+fn parse_unix_id(value: &LuaTable, key: &'static str) -> LuaResult<Option<u32>> {
+    let id = match value.get(key)? {
+        LuaValue::Nil => return Ok(None),
+        LuaValue::Integer(i) => u32::try_from(i).ok(),
+        LuaValue::Number(n) if n.fract() == 0.0 && n >= 0.0 && n <= f64::from(u32::MAX) => {
+            Some(n as u32)
+        }
+        LuaValue::Number(_) => None,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid type for option '{key}' - expected 'number', got '{}'",
+                value.type_name()
+            )))
+        }
+    };
+    let id = id.ok_or_else(|| {
+        LuaError::RuntimeError(format!(
+            "Invalid value for option '{key}' - expected a non-negative integer"
+        ))
+    })?;
+    if cfg!(unix) {
+        Ok(Some(id))
+    } else {
+        Err(LuaError::RuntimeError(format!(
+            "Invalid option '{key}' - only supported on Unix"
+        )))
+    }
+}
+
+fn parse_umask(value: &LuaTable) -> LuaResult<Option<u32>> {
+    /*
+        NOTE: Luau has no octal number literals, so we also accept
+        the umask as a string of octal digits, such as "022" or "0o022"
+    */
+    let umask = match value.get("umask")? {
+        LuaValue::String(s) => {
+            let s = s.to_str()?.trim();
+            let digits = s.strip_prefix("0o").unwrap_or(s);
+            u32::from_str_radix(digits, 8).map_err(|_| {
+                LuaError::RuntimeError(format!(
+                    "Invalid value for option 'umask' - expected a string of octal digits, got '{s}'"
+                ))
+            })?
+        }
+        _ => match parse_unix_id(value, "umask")? {
+            Some(umask) => umask,
+            None => return Ok(None),
+        },
+    };
+    if umask > 0o777 {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid value for option 'umask' - expected at most 0o777, got 0o{umask:o}"
+        )));
+    }
+    if cfg!(unix) {
+        Ok(Some(umask))
+    } else {
+        Err(LuaError::runtime(
+            "Invalid option 'umask' - only supported on Unix",
+        ))
+    }
+}
@@ -0,0 +1,78 @@
+use mlua::prelude::*;
+
+use super::{ProcessSpawnOptions, ProcessSpawnOptionsStdioKind};
+
+/**
+    A single stage of a process pipeline, given as `{ program, args, options }`.
+*/
+#[derive(Debug, Clone)]
+pub struct ProcessPipelineStage {
+    pub program: String,
+    pub args: Option<Vec<String>>,
+    pub options: ProcessSpawnOptions,
+}
+
+impl<'lua> FromLua<'lua> for ProcessPipelineStage {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(t) = value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ProcessPipelineStage",
+                message: Some(format!(
+                    "Invalid pipeline stage - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        let program = match t.raw_get(1)? {
+            LuaValue::String(s) => s.to_str()?.to_string(),
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid type for pipeline stage program - expected 'string', got '{}'",
+                    value.type_name()
+                )))
+            }
+        };
+
+        Ok(Self {
+            program,
+            args: t.raw_get(2)?,
+            options: t.raw_get(3)?,
+        })
+    }
+}
+
+impl ProcessPipelineStage {
+    /**
+        Validates the options of this stage, given its position in the pipeline.
+
+        Only the first stage may be given stdin, and only the last stage may
+        have its stdout redirected, since all other stdin and stdout streams
+        are used to connect the stages of the pipeline to each other.
+    */
+    pub fn validate(&self, index: usize, num_stages: usize) -> LuaResult<()> {
+        let number = index + 1;
+        if self.options.pty.is_some() {
+            return Err(LuaError::RuntimeError(format!(
+                "Option 'pty' can not be used for stages of a pipeline, got it for stage #{number}"
+            )));
+        }
+        if index > 0 && self.options.stdio.stdin.is_some() {
+            return Err(LuaError::RuntimeError(format!(
+                "Option 'stdin' can only be used for the first stage of a pipeline, got it for stage #{number}"
+            )));
+        }
+        if index + 1 < num_stages
+            && !matches!(
+                self.options.stdio.stdout,
+                ProcessSpawnOptionsStdioKind::Default
+            )
+        {
+            return Err(LuaError::RuntimeError(format!(
+                "Option 'stdout' can only be used for the last stage of a pipeline, got it for stage #{number}"
+            )));
+        }
+        Ok(())
+    }
+}
@@ -12,7 +12,7 @@ pub struct ProcessSpawnOptionsStdio {
 impl From<ProcessSpawnOptionsStdioKind> for ProcessSpawnOptionsStdio {
     fn from(value: ProcessSpawnOptionsStdioKind) -> Self {
         Self {
-            stdout: value,
+            stdout: value.clone(),
             stderr: value,
             ..Default::default()
         }
@@ -0,0 +1,139 @@
+use std::{io::Result as IoResult, process::Stdio, sync::Arc};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSchedulerExt;
+use tokio::{io::AsyncRead, sync::mpsc, sync::watch};
+
+use super::options::ProcessSpawnOptionsStdioKind;
+
+pub(super) type OutputReader = Box<dyn AsyncRead + Send + Unpin>;
+pub(super) type OutputCallbackSender = mpsc::UnboundedSender<(Arc<LuaRegistryKey>, Vec<u8>)>;
+pub(super) type OutputCallbackReceiver = mpsc::UnboundedReceiver<(Arc<LuaRegistryKey>, Vec<u8>)>;
+
+const CALLBACK_DRIVER_KEY: &str = "__process_output_callback_driver";
+const CALLBACK_DRIVER_SOURCE: &str = r"
+local callback, chunk = ...
+return pcall(callback, chunk)
+";
+
+/**
+    Stdio for the output streams of a child process, created from stdio kinds.
+*/
+pub(super) struct ChildOutputs {
+    pub stdout: Stdio,
+    pub stderr: Stdio,
+    pub merged: Option<OutputReader>,
+}
+
+impl ChildOutputs {
+    /**
+        Creates stdio for the given output stream kinds.
+
+        Any streams using the `Merged` kind are given the write end of
+        one single shared pipe, which guarantees that the merged output
+        is read in the exact same order that the child wrote it in.
+    */
+    pub fn new(
+        stdout_kind: &ProcessSpawnOptionsStdioKind,
+        stderr_kind: &ProcessSpawnOptionsStdioKind,
+    ) -> IoResult<Self> {
+        let stdout_merged = *stdout_kind == ProcessSpawnOptionsStdioKind::Merged;
+        let stderr_merged = *stderr_kind == ProcessSpawnOptionsStdioKind::Merged;
+        if !stdout_merged && !stderr_merged {
+            return Ok(Self {
+                stdout: stdout_kind.as_stdio()?,
+                stderr: stderr_kind.as_stdio()?,
+                merged: None,
+            });
+        }
+
+        let (reader, writer) = std::io::pipe()?;
+        let stdout = if stdout_merged {
+            writer.try_clone()?.into()
+        } else {
+            stdout_kind.as_stdio()?
+        };
+        let stderr = if stderr_merged {
+            writer.into()
+        } else {
+            stderr_kind.as_stdio()?
+        };
+
+        Ok(Self {
+            stdout,
+            stderr,
+            merged: Some(merged_reader(reader)?),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn merged_reader(reader: std::io::PipeReader) -> IoResult<OutputReader> {
+    use std::os::fd::OwnedFd;
+    use tokio::net::unix::pipe::Receiver;
+    let receiver = Receiver::from_owned_fd(OwnedFd::from(reader))?;
+    Ok(Box::new(receiver))
+}
+
+#[cfg(windows)]
+fn merged_reader(reader: std::io::PipeReader) -> IoResult<OutputReader> {
+    use std::os::windows::io::OwnedHandle;
+    let file = std::fs::File::from(OwnedHandle::from(reader));
+    Ok(Box::new(tokio::fs::File::from_std(file)))
+}
+
+/**
+    Calls output callbacks with chunks sent by child processes, until all
+    of the processes sending chunks have had their outputs fully read.
+
+    Callbacks are run as separate threads in the scheduler, so they may
+    yield, but only a single callback is ever running at the same time.
+
+    If any callback errors, the processes are cancelled using the given
+    cancellation sender, and the error is returned after that.
+*/
+pub(super) async fn run_output_callbacks(
+    lua: &Lua,
+    mut callback_rx: OutputCallbackReceiver,
+    cancel_tx: &watch::Sender<bool>,
+) -> LuaResult<()> {
+    while let Some((key, chunk)) = callback_rx.recv().await {
+        if let Err(e) = run_output_callback(lua, &key, chunk).await {
+            cancel_tx.send_replace(true);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+async fn run_output_callback(lua: &Lua, key: &LuaRegistryKey, chunk: Vec<u8>) -> LuaResult<()> {
+    let driver = if let Some(driver) = lua.named_registry_value(CALLBACK_DRIVER_KEY)? {
+        driver
+    } else {
+        let driver = lua
+            .load(CALLBACK_DRIVER_SOURCE)
+            .set_name("process output callback")
+            .into_function()?;
+        lua.set_named_registry_value(CALLBACK_DRIVER_KEY, driver.clone())?;
+        driver
+    };
+    let callback: LuaFunction = lua.registry_value(key)?;
+
+    // NOTE: The callback is called using pcall in the driver, since errors
+    // are rethrown to the caller, and should not also be reported by the
+    // scheduler as an uncaught error in a separate thread
+    let thread_id = lua.push_thread_back(driver, (callback, lua.create_string(chunk)?))?;
+    lua.track_thread(thread_id);
+    lua.wait_for_thread(thread_id).await;
+    let mut values = lua
+        .get_thread_result(thread_id)
+        .expect("Missing output callback thread result")?
+        .into_iter();
+
+    match (values.next(), values.next()) {
+        (Some(LuaValue::Boolean(true)), _) => Ok(()),
+        (_, Some(LuaValue::Error(e))) => Err(e),
+        (_, Some(value)) => Err(LuaError::RuntimeError(value.to_string()?)),
+        (_, None) => Err(LuaError::runtime("Output callback failed")),
+    }
+}
@@ -0,0 +1,118 @@
+use std::{future::Future, process::Stdio, time::Instant};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use tokio::io::AsyncWriteExt;
+
+use super::{
+    options::{ProcessPipelineStage, ProcessSpawnOptionsStdioKind},
+    output::{ChildOutputs, OutputCallbackSender},
+    wait_for_child::{wait_for_child, WaitForChildLimits, WaitForChildOutputs, WaitForChildResult},
+};
+
+/**
+    Spawns all stages of a pipeline, connecting the stdout of each
+    stage directly to the stdin of the next one, and returns a future
+    that waits for all stages to exit.
+
+    Stdout is only read for the last stage, stdin is only written
+    for the first stage, and stderr is read for every stage, all
+    according to the stdio options given for those stages.
+*/
+pub(super) fn run_pipeline<F>(
+    lua: &Lua,
+    stages: Vec<ProcessPipelineStage>,
+    callback_tx: OutputCallbackSender,
+    cancelled: impl Fn() -> F,
+) -> LuaResult<impl Future<Output = LuaResult<Vec<WaitForChildResult>>>>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let num_stages = stages.len();
+    let mut previous_stdout: Option<Stdio> = None;
+    let mut first_stdin = None;
+    let mut waits = Vec::with_capacity(num_stages);
+
+    for (index, mut stage) in stages.into_iter().enumerate() {
+        let is_first = index == 0;
+        let is_last = index == num_stages - 1;
+
+        let stdout_kind = if is_last {
+            std::mem::take(&mut stage.options.stdio.stdout)
+        } else {
+            // NOTE: Stdout of this stage is given to the next stage,
+            // and will never be read by us, so we can ignore it here
+            ProcessSpawnOptionsStdioKind::None
+        };
+        let stderr_kind = std::mem::take(&mut stage.options.stdio.stderr);
+        let stdin = stage.options.stdio.stdin.take();
+        let limits = WaitForChildLimits {
+            timeout: stage.options.timeout,
+            kill_grace_period: stage.options.kill_grace_period,
+        };
+
+        let stdin_stdio = match previous_stdout.take() {
+            Some(stdio) => stdio,
+            None if is_first && stdin.is_some() => Stdio::piped(),
+            None => Stdio::null(),
+        };
+        let child_outputs = ChildOutputs::new(&stdout_kind, &stderr_kind).into_lua_err()?;
+        let stdout_stdio = if is_last {
+            child_outputs.stdout
+        } else {
+            Stdio::piped()
+        };
+
+        let started = Instant::now();
+        let mut child = stage
+            .options
+            .into_command(stage.program, stage.args)
+            .stdin(stdin_stdio)
+            .stdout(stdout_stdio)
+            .stderr(child_outputs.stderr)
+            .kill_on_drop(true)
+            .spawn()?;
+
+        if !is_last {
+            let stdout = child.stdout.take().expect("stdout was piped");
+            previous_stdout = Some(stdout.try_into().into_lua_err()?);
+        }
+        if is_first {
+            if let Some(stdin) = stdin {
+                first_stdin = Some((child.stdin.take().expect("stdin was piped"), stdin));
+            }
+        }
+
+        let outputs = WaitForChildOutputs {
+            stdout_kind,
+            stderr_kind,
+            merged: child_outputs.merged,
+            pty: None,
+            callback_tx: callback_tx.clone(),
+        };
+        waits.push(wait_for_child(child, outputs, limits, started, cancelled()));
+    }
+
+    // NOTE: Stdin must be written while the stages are running, since the
+    // pipeline may otherwise fill up and block before we finish writing
+    if let Some((mut child_stdin, stdin)) = first_stdin {
+        lua.spawn(async move {
+            // NOTE: Errors here mean the first stage exited without reading all of its
+            // input, which is fine, and is also how a shell would handle that situation
+            child_stdin.write_all(&stdin).await.ok();
+        })
+        .detach();
+    }
+
+    let handles = waits
+        .into_iter()
+        .map(|wait| lua.spawn(wait))
+        .collect::<Vec<_>>();
+    Ok(async move {
+        let mut results = Vec::with_capacity(num_stages);
+        for handle in handles {
+            results.push(handle.await?);
+        }
+        Ok(results)
+    })
+}
@@ -0,0 +1,326 @@
+use mlua::prelude::*;
+
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 80;
+
+/**
+    The size of a pseudo-terminal, in characters.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtySize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl Default for PtySize {
+    fn default() -> Self {
+        Self {
+            rows: DEFAULT_ROWS,
+            cols: DEFAULT_COLS,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for PtySize {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(t) = value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "PtySize",
+                message: Some(format!(
+                    "Invalid pty size - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+        let parse = |key: &'static str, default: u16| -> LuaResult<u16> {
+            match t.get::<_, Option<LuaNumber>>(key)? {
+                None => Ok(default),
+                Some(n) if n.fract() == 0.0 && n >= 1.0 && n <= f64::from(u16::MAX) =>
+                {
+                    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                    Ok(n as u16)
+                }
+                Some(n) => Err(LuaError::RuntimeError(format!(
+                    "Invalid pty size - expected '{key}' to be a positive integer, got {n}"
+                ))),
+            }
+        };
+        Ok(Self {
+            rows: parse("rows", DEFAULT_ROWS)?,
+            cols: parse("cols", DEFAULT_COLS)?,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use self::linux::{PtyHandle, PtyStream};
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        ffi::CStr,
+        fs::{File, OpenOptions},
+        io::{self, Read, Result as IoResult, Write},
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::fs::OpenOptionsExt,
+        },
+        pin::Pin,
+        process::Stdio,
+        task::{ready, Context, Poll},
+    };
+
+    use tokio::{
+        io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf},
+        process::{Child, Command},
+    };
+
+    use super::PtySize;
+
+    fn check(res: libc::c_int) -> IoResult<libc::c_int> {
+        if res < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(res)
+        }
+    }
+
+    /**
+        The controlling side of a pseudo-terminal, which
+        a child process was spawned to run under.
+    */
+    #[derive(Debug)]
+    pub struct PtyHandle {
+        master: OwnedFd,
+    }
+
+    impl PtyHandle {
+        fn open() -> IoResult<Self> {
+            // SAFETY: These are plain libc calls that either return a new file
+            // descriptor that we take ownership of right away, or fail cleanly
+            let master = unsafe {
+                let fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+                OwnedFd::from_raw_fd(fd)
+            };
+            let fd = master.as_raw_fd();
+            // SAFETY: The file descriptor is valid and owned by us for the duration of these calls
+            unsafe {
+                check(libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC))?;
+                let flags = check(libc::fcntl(fd, libc::F_GETFL))?;
+                check(libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+                check(libc::grantpt(fd))?;
+                check(libc::unlockpt(fd))?;
+            }
+            Ok(Self { master })
+        }
+
+        fn open_slave(&self) -> IoResult<OwnedFd> {
+            let mut name = [0 as libc::c_char; 128];
+            // SAFETY: The buffer is valid for writes of its full length
+            let res =
+                unsafe { libc::ptsname_r(self.master.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+            if res != 0 {
+                return Err(io::Error::from_raw_os_error(res));
+            }
+            // SAFETY: ptsname_r succeeded, so the buffer now contains a nul-terminated path
+            let path = unsafe { CStr::from_ptr(name.as_ptr()) };
+            let slave = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_NOCTTY)
+                .open(path.to_string_lossy().as_ref())?;
+            Ok(OwnedFd::from(slave))
+        }
+
+        /**
+            Spawns the given command under a new pseudo-terminal, with
+            all of its stdio connected to the terminal, and the terminal
+            set as its controlling terminal in a new session.
+        */
+        pub fn spawn(mut command: Command, size: PtySize) -> IoResult<(Child, Self)> {
+            let pty = Self::open()?;
+            pty.resize(size)?;
+
+            let slave = pty.open_slave()?;
+            command
+                .stdin(Stdio::from(slave.try_clone()?))
+                .stdout(Stdio::from(slave.try_clone()?))
+                .stderr(Stdio::from(slave));
+
+            // SAFETY: The closure runs in the forked child before exec, and only
+            // calls setsid and ioctl, which are both async-signal-safe functions
+            unsafe {
+                command.pre_exec(|| {
+                    check(libc::setsid())?;
+                    check(libc::ioctl(0, libc::TIOCSCTTY, 0))?;
+                    Ok(())
+                });
+            }
+
+            // NOTE: The command must be dropped right after spawning, so that our
+            // copies of the terminal are closed and we see when the child exits
+            let child = command.spawn()?;
+            drop(command);
+
+            Ok((child, pty))
+        }
+
+        pub fn resize(&self, size: PtySize) -> IoResult<()> {
+            let winsize = libc::winsize {
+                ws_row: size.rows,
+                ws_col: size.cols,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            // SAFETY: The file descriptor is valid, and winsize is the expected argument type
+            unsafe {
+                check(libc::ioctl(
+                    self.master.as_raw_fd(),
+                    libc::TIOCSWINSZ,
+                    &winsize,
+                ))?;
+            }
+            Ok(())
+        }
+
+        pub fn stream(&self) -> IoResult<PtyStream> {
+            let file = File::from(self.master.try_clone()?);
+            Ok(PtyStream {
+                inner: AsyncFd::new(file)?,
+            })
+        }
+    }
+
+    /**
+        An async stream for reading output from, and writing input to, a pseudo-terminal.
+    */
+    #[derive(Debug)]
+    pub struct PtyStream {
+        inner: AsyncFd<File>,
+    }
+
+    impl AsyncRead for PtyStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<IoResult<()>> {
+            loop {
+                let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+                let unfilled = buf.initialize_unfilled();
+                match guard.try_io(|inner| inner.get_ref().read(unfilled)) {
+                    Ok(Ok(len)) => {
+                        buf.advance(len);
+                        return Poll::Ready(Ok(()));
+                    }
+                    // NOTE: Reading fails with EIO once all processes using the
+                    // terminal have exited, which is the end of the output for us
+                    Ok(Err(e)) if e.raw_os_error() == Some(libc::EIO) => {
+                        return Poll::Ready(Ok(()));
+                    }
+                    Ok(Err(e)) => return Poll::Ready(Err(e)),
+                    Err(_would_block) => {}
+                }
+            }
+        }
+    }
+
+    impl AsyncWrite for PtyStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<IoResult<usize>> {
+            loop {
+                let mut guard = ready!(self.inner.poll_write_ready(cx))?;
+                match guard.try_io(|inner| inner.get_ref().write(buf)) {
+                    Ok(res) => return Poll::Ready(res),
+                    Err(_would_block) => {}
+                }
+            }
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub use self::unsupported::{PtyHandle, PtyStream};
+
+#[cfg(not(target_os = "linux"))]
+mod unsupported {
+    use std::{
+        io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use tokio::{
+        io::{AsyncRead, AsyncWrite, ReadBuf},
+        process::{Child, Command},
+    };
+
+    use super::PtySize;
+
+    fn unsupported() -> IoError {
+        IoError::new(
+            IoErrorKind::Unsupported,
+            "Pseudo-terminals are only supported on Linux",
+        )
+    }
+
+    #[derive(Debug)]
+    pub enum PtyHandle {}
+
+    #[derive(Debug)]
+    pub enum PtyStream {}
+
+    impl PtyHandle {
+        pub fn spawn(_: Command, _: PtySize) -> IoResult<(Child, Self)> {
+            Err(unsupported())
+        }
+
+        pub fn resize(&self, _: PtySize) -> IoResult<()> {
+            match *self {}
+        }
+
+        pub fn stream(&self) -> IoResult<PtyStream> {
+            match *self {}
+        }
+    }
+
+    impl AsyncRead for PtyStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &mut ReadBuf<'_>,
+        ) -> Poll<IoResult<()>> {
+            match *self.get_mut() {}
+        }
+    }
+
+    impl AsyncWrite for PtyStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &[u8],
+        ) -> Poll<IoResult<usize>> {
+            match *self.get_mut() {}
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+            match *self.get_mut() {}
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+            match *self.get_mut() {}
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+/**
+    Resources used by a process, such as its peak memory usage and CPU time.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub max_rss: u64,
+    pub user_time: Duration,
+    pub system_time: Duration,
+}
+
+impl ResourceUsage {
+    /**
+        Gets the resource usage of the current process, if supported on the current platform.
+    */
+    pub fn current() -> Option<Self> {
+        #[cfg(unix)]
+        {
+            // SAFETY: getrusage only writes to the given struct, which is valid for writes
+            let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+            let res = unsafe { libc::getrusage(libc::RUSAGE_SELF, std::ptr::addr_of_mut!(usage)) };
+            (res == 0).then(|| Self::from_rusage(&usage))
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    #[cfg(unix)]
+    fn from_rusage(usage: &libc::rusage) -> Self {
+        let time = |tv: libc::timeval| {
+            Duration::from_secs(u64::try_from(tv.tv_sec).unwrap_or_default())
+                + Duration::from_micros(u64::try_from(tv.tv_usec).unwrap_or_default())
+        };
+        // NOTE: Max RSS is given in kilobytes on most platforms, but in bytes on macOS
+        let max_rss = u64::try_from(usage.ru_maxrss).unwrap_or_default();
+        let max_rss = if cfg!(target_os = "macos") {
+            max_rss
+        } else {
+            max_rss * 1024
+        };
+        Self {
+            max_rss,
+            user_time: time(usage.ru_utime),
+            system_time: time(usage.ru_stime),
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for ResourceUsage {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        #[allow(clippy::cast_precision_loss)]
+        let max_rss = self.max_rss as f64;
+        TableBuilder::new(lua)?
+            .with_value("maxRss", max_rss)?
+            .with_value("userTime", self.user_time.as_secs_f64())?
+            .with_value("systemTime", self.system_time.as_secs_f64())?
+            .build_readonly()?
+            .into_lua(lua)
+    }
+}
+
+/**
+    Waits for the child process with the given pid to exit, and gets its resource usage,
+    without reaping the child - it must still be waited for normally afterwards.
+
+    Returns `None` if the resource usage could not be read, in
+    which case the child may or may not have exited already.
+*/
+#[cfg(target_os = "linux")]
+pub async fn wait_for_child_usage(pid: u32) -> Option<ResourceUsage> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use tokio::io::unix::AsyncFd;
+
+    let pid = libc::pid_t::try_from(pid).ok()?;
+
+    // NOTE: A pidfd becomes readable once the process has exited, which lets us
+    // wait for it to exit without also reaping it, unlike waiting for its status
+    // SAFETY: The syscall either fails, or returns a new file descriptor that we own
+    let pidfd = unsafe {
+        let fd = libc::syscall(libc::SYS_pidfd_open, pid, 0);
+        let fd = libc::c_int::try_from(fd).ok().filter(|fd| *fd >= 0)?;
+        OwnedFd::from_raw_fd(fd)
+    };
+    let pidfd = AsyncFd::new(pidfd).ok()?;
+    pidfd.readable().await.ok()?.retain_ready();
+
+    // NOTE: The libc wrapper for waitid does not expose the resource usage
+    // argument of the syscall, so we call it directly, using WNOWAIT to leave
+    // the child as a zombie that the normal wait for its status will reap later
+    // SAFETY: Both structs are valid for writes, and are only read if the syscall succeeded
+    let mut info = unsafe { std::mem::zeroed::<libc::siginfo_t>() };
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_waitid,
+            libc::P_PID,
+            pid,
+            std::ptr::addr_of_mut!(info),
+            libc::WEXITED | libc::WNOWAIT,
+            std::ptr::addr_of_mut!(usage),
+        )
+    };
+    (res == 0).then(|| ResourceUsage::from_rusage(&usage))
+}
+
+#[cfg(not(target_os = "linux"))]
+#[allow(clippy::unused_async)]
+pub async fn wait_for_child_usage(_: u32) -> Option<ResourceUsage> {
+    None
+}
@@ -0,0 +1,217 @@
+use std::{fmt, io::Result as IoResult, process::ExitStatus, str::FromStr};
+
+use mlua::prelude::*;
+use tokio::process::Child;
+
+/**
+    A signal that can be sent to a child process.
+
+    Signals other than `Kill` are only supported on Unix, on other
+    platforms the process will always be forcefully terminated.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ProcessSignal {
+    Hangup,
+    Interrupt,
+    Quit,
+    #[default]
+    Kill,
+    User1,
+    User2,
+    Terminate,
+}
+
+impl ProcessSignal {
+    pub fn all() -> &'static [Self] {
+        &[
+            Self::Hangup,
+            Self::Interrupt,
+            Self::Quit,
+            Self::Kill,
+            Self::User1,
+            Self::User2,
+            Self::Terminate,
+        ]
+    }
+
+    #[cfg(unix)]
+    pub fn as_raw(self) -> i32 {
+        match self {
+            Self::Hangup => libc::SIGHUP,
+            Self::Interrupt => libc::SIGINT,
+            Self::Quit => libc::SIGQUIT,
+            Self::Kill => libc::SIGKILL,
+            Self::User1 => libc::SIGUSR1,
+            Self::User2 => libc::SIGUSR2,
+            Self::Terminate => libc::SIGTERM,
+        }
+    }
+
+    /**
+        Gets the conventional exit code for a process that was
+        terminated by this signal, which is 128 + the signal number.
+    */
+    pub fn exit_code(self) -> u8 {
+        #[cfg(unix)]
+        {
+            (128 + self.as_raw()) as u8
+        }
+
+        #[cfg(not(unix))]
+        {
+            1
+        }
+    }
+}
+
+impl fmt::Display for ProcessSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match *self {
+            Self::Hangup => "SIGHUP",
+            Self::Interrupt => "SIGINT",
+            Self::Quit => "SIGQUIT",
+            Self::Kill => "SIGKILL",
+            Self::User1 => "SIGUSR1",
+            Self::User2 => "SIGUSR2",
+            Self::Terminate => "SIGTERM",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ProcessSignal {
+    type Err = LuaError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.trim().to_ascii_uppercase();
+        let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+        Ok(match name {
+            "HUP" => Self::Hangup,
+            "INT" => Self::Interrupt,
+            "QUIT" => Self::Quit,
+            "KILL" => Self::Kill,
+            "USR1" => Self::User1,
+            "USR2" => Self::User2,
+            "TERM" => Self::Terminate,
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid signal - got '{}', expected one of {}",
+                    s,
+                    ProcessSignal::all()
+                        .iter()
+                        .map(|k| format!("'{k}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )))
+            }
+        })
+    }
+}
+
+impl<'lua> FromLua<'lua> for ProcessSignal {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => s.to_str()?.parse(),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ProcessSignal",
+                message: Some(format!(
+                    "Invalid signal - expected string, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Sends a signal to the given child process.
+
+    Does nothing if the child process has already been waited for.
+*/
+pub fn send_signal(child: &mut Child, signal: ProcessSignal) -> IoResult<()> {
+    #[cfg(unix)]
+    if signal != ProcessSignal::Kill {
+        // NOTE: The id is only None once the child has been reaped,
+        // which makes sure we never signal some unrelated process
+        let Some(pid) = child.id() else {
+            return Ok(());
+        };
+        // SAFETY: Sending a signal has no memory safety requirements
+        let res = unsafe { libc::kill(pid as libc::pid_t, signal.as_raw()) };
+        return if res == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        };
+    }
+
+    #[cfg(not(unix))]
+    let _ = signal;
+
+    child.start_kill()
+}
+
+/**
+    Gets the signal that terminated a process, if any, and
+    whether or not the process also produced a core dump.
+
+    Always returns `None` and `false` on platforms other than Unix.
+*/
+pub fn exit_status_signal(status: ExitStatus) -> (Option<i32>, bool) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        (status.signal(), status.core_dumped())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        (None, false)
+    }
+}
+
+/**
+    Gets the name of the given raw signal number, such as `SIGSEGV`.
+*/
+#[cfg(unix)]
+pub fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGURG => "SIGURG",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGVTALRM => "SIGVTALRM",
+        libc::SIGPROF => "SIGPROF",
+        libc::SIGWINCH => "SIGWINCH",
+        libc::SIGIO => "SIGIO",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn signal_name(_: i32) -> Option<&'static str> {
+    None
+}
@@ -1,73 +1,285 @@
-use std::process::ExitStatus;
+use std::{
+    future::Future,
+    process::ExitStatus,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use mlua::prelude::*;
 use tokio::{
-    io::{self, AsyncRead, AsyncReadExt},
+    io::{self, AsyncRead, AsyncReadExt, AsyncWriteExt},
     process::Child,
     task,
+    time::{sleep, timeout},
+};
+
+use super::{
+    options::ProcessSpawnOptionsStdioKind,
+    output::{OutputCallbackSender, OutputReader},
+    pty::PtyStream,
+    resource_usage::{wait_for_child_usage, ResourceUsage},
+    signal::{send_signal, ProcessSignal},
 };
 
-use super::{options::ProcessSpawnOptionsStdioKind, tee_writer::AsyncTeeWriter};
+/**
+    How long to wait for any remaining output after the child process
+    has been forcefully terminated - the output streams may be held open
+    by grandchildren of the process, which we can't reliably terminate.
+*/
+const TERMINATED_OUTPUT_TIMEOUT: Duration = Duration::from_millis(250);
+
+type SharedBuffer = Arc<Mutex<Vec<u8>>>;
 
 #[derive(Debug, Clone)]
 pub(super) struct WaitForChildResult {
     pub status: ExitStatus,
     pub stdout: Vec<u8>,
     pub stderr: Vec<u8>,
+    pub output: Option<Vec<u8>>,
+    pub timed_out: bool,
+    pub pid: Option<u32>,
+    pub duration: Duration,
+    pub resource_usage: Option<ResourceUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct WaitForChildLimits {
+    pub timeout: Option<Duration>,
+    pub kill_grace_period: Option<Duration>,
+}
+
+/**
+    How to handle the output streams of a child process that is being waited for.
+
+    Any chunks for streams using the `Callback` kind are sent using `callback_tx`, and
+    if the child was spawned under a pseudo-terminal, its output is read as stdout.
+*/
+pub(super) struct WaitForChildOutputs {
+    pub stdout_kind: ProcessSpawnOptionsStdioKind,
+    pub stderr_kind: ProcessSpawnOptionsStdioKind,
+    pub merged: Option<OutputReader>,
+    pub pty: Option<PtyStream>,
+    pub callback_tx: OutputCallbackSender,
+}
+
+enum WaitOutcome {
+    Exited(ExitStatus, Option<ResourceUsage>),
+    TimedOut,
+    Cancelled,
 }
 
 async fn read_with_stdio_kind<R>(
     read_from: Option<R>,
     kind: ProcessSpawnOptionsStdioKind,
-) -> LuaResult<Vec<u8>>
+    buffer: SharedBuffer,
+    callback_tx: OutputCallbackSender,
+) -> LuaResult<()>
 where
     R: AsyncRead + Unpin,
 {
-    Ok(match kind {
-        ProcessSpawnOptionsStdioKind::None | ProcessSpawnOptionsStdioKind::Forward => Vec::new(),
-        ProcessSpawnOptionsStdioKind::Default => {
-            let mut read_from =
-                read_from.expect("read_from must be Some when stdio kind is Default");
+    match kind {
+        ProcessSpawnOptionsStdioKind::None
+        | ProcessSpawnOptionsStdioKind::Forward
+        | ProcessSpawnOptionsStdioKind::Merged
+        | ProcessSpawnOptionsStdioKind::File { .. } => return Ok(()),
+        ProcessSpawnOptionsStdioKind::Default
+        | ProcessSpawnOptionsStdioKind::Inherit
+        | ProcessSpawnOptionsStdioKind::Callback { .. } => {}
+    }
 
-            let mut buffer = Vec::new();
+    let mut read_from = read_from.expect("read_from must be Some when stdio kind is piped");
 
-            read_from.read_to_end(&mut buffer).await.into_lua_err()?;
+    // NOTE: We read in chunks and store them in a shared buffer, instead
+    // of reading until the end, so that any output read so far is still
+    // available if we have to stop reading before the stream ends
+    let mut stdout = io::stdout();
+    let mut chunk = vec![0; 8192];
+    let mut pending_line = Vec::new();
+    loop {
+        let len = read_from.read(&mut chunk).await.into_lua_err()?;
+        if len == 0 {
+            break;
+        }
+        // NOTE: Send errors for callbacks mean that the receiving end
+        // has stopped calling callbacks, and the child is being cancelled
+        if let ProcessSpawnOptionsStdioKind::Callback { callback, lines } = &kind {
+            if *lines {
+                pending_line.extend_from_slice(&chunk[..len]);
+                while let Some(pos) = pending_line.iter().position(|b| *b == b'\n') {
+                    let mut line = pending_line.drain(..=pos).collect::<Vec<_>>();
+                    line.pop();
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    callback_tx.send((Arc::clone(callback), line)).ok();
+                }
+            } else {
+                callback_tx
+                    .send((Arc::clone(callback), chunk[..len].to_vec()))
+                    .ok();
+            }
+            continue;
+        }
+        if kind == ProcessSpawnOptionsStdioKind::Inherit {
+            stdout.write_all(&chunk[..len]).await.into_lua_err()?;
+            stdout.flush().await.into_lua_err()?;
+        }
+        buffer
+            .lock()
+            .expect("Failed to lock output buffer")
+            .extend_from_slice(&chunk[..len]);
+    }
 
-            buffer
+    // NOTE: The last line may not end with a newline, but should still be delivered
+    if let ProcessSpawnOptionsStdioKind::Callback { callback, .. } = &kind {
+        if !pending_line.is_empty() {
+            callback_tx.send((Arc::clone(callback), pending_line)).ok();
         }
-        ProcessSpawnOptionsStdioKind::Inherit => {
-            let mut read_from =
-                read_from.expect("read_from must be Some when stdio kind is Inherit");
+    }
 
-            let mut stdout = io::stdout();
-            let mut tee = AsyncTeeWriter::new(&mut stdout);
+    Ok(())
+}
 
-            io::copy(&mut read_from, &mut tee).await.into_lua_err()?;
+/**
+    Waits for the child process to exit, also getting its
+    resource usage, if supported on the current platform.
+*/
+async fn wait_with_usage(child: &mut Child) -> LuaResult<(ExitStatus, Option<ResourceUsage>)> {
+    let usage = match child.id() {
+        Some(pid) => wait_for_child_usage(pid).await,
+        None => None,
+    };
+    let status = child.wait().await.into_lua_err()?;
+    Ok((status, usage))
+}
 
-            tee.into_vec()
+/**
+    Terminates the child process, first asking it nicely using
+    `SIGTERM` if a grace period was given, and then using `SIGKILL`.
+*/
+async fn terminate_child(
+    child: &mut Child,
+    grace_period: Option<Duration>,
+) -> LuaResult<(ExitStatus, Option<ResourceUsage>)> {
+    if let Some(grace_period) = grace_period {
+        send_signal(child, ProcessSignal::Terminate).into_lua_err()?;
+        if let Ok(res) = timeout(grace_period, wait_with_usage(child)).await {
+            return res;
         }
-    })
+    }
+    child.start_kill().into_lua_err()?;
+    wait_with_usage(child).await
 }
 
 pub(super) async fn wait_for_child(
     mut child: Child,
-    stdout_kind: ProcessSpawnOptionsStdioKind,
-    stderr_kind: ProcessSpawnOptionsStdioKind,
+    outputs: WaitForChildOutputs,
+    limits: WaitForChildLimits,
+    started: Instant,
+    cancelled: impl Future<Output = ()>,
 ) -> LuaResult<WaitForChildResult> {
-    let stdout_opt = child.stdout.take();
+    let pid = child.id();
+    let stdout_opt: Option<OutputReader> = match outputs.pty {
+        Some(pty) => Some(Box::new(pty)),
+        None => child
+            .stdout
+            .take()
+            .map(|stdout| Box::new(stdout) as OutputReader),
+    };
     let stderr_opt = child.stderr.take();
 
-    let stdout_task = task::spawn(read_with_stdio_kind(stdout_opt, stdout_kind));
-    let stderr_task = task::spawn(read_with_stdio_kind(stderr_opt, stderr_kind));
+    let stdout_buffer = SharedBuffer::default();
+    let stderr_buffer = SharedBuffer::default();
+    let (merged_kind, merged_buffer) = if outputs.merged.is_some() {
+        (
+            ProcessSpawnOptionsStdioKind::Default,
+            Some(SharedBuffer::default()),
+        )
+    } else {
+        (ProcessSpawnOptionsStdioKind::None, None)
+    };
+
+    let mut stdout_task = task::spawn(read_with_stdio_kind(
+        stdout_opt,
+        outputs.stdout_kind,
+        Arc::clone(&stdout_buffer),
+        outputs.callback_tx.clone(),
+    ));
+    let mut stderr_task = task::spawn(read_with_stdio_kind(
+        stderr_opt,
+        outputs.stderr_kind,
+        Arc::clone(&stderr_buffer),
+        outputs.callback_tx.clone(),
+    ));
+    let mut merged_task = task::spawn(read_with_stdio_kind(
+        outputs.merged,
+        merged_kind,
+        merged_buffer.clone().unwrap_or_default(),
+        outputs.callback_tx,
+    ));
+
+    let timed_out = async {
+        match limits.timeout {
+            Some(duration) => sleep(duration).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    // NOTE: We can't terminate the child in the select branches below
+    // since the wait future borrows it, so we only note the outcome here
+    let outcome = tokio::select! {
+        res = wait_with_usage(&mut child) => {
+            let (status, usage) = res?;
+            WaitOutcome::Exited(status, usage)
+        }
+        () = timed_out => WaitOutcome::TimedOut,
+        () = cancelled => WaitOutcome::Cancelled,
+    };
 
-    let status = child.wait().await.expect("Child process failed to start");
+    let (status, resource_usage, terminated, timed_out) = match outcome {
+        WaitOutcome::Exited(status, usage) => (status, usage, false, false),
+        WaitOutcome::TimedOut | WaitOutcome::Cancelled => {
+            let (status, usage) = terminate_child(&mut child, limits.kill_grace_period).await?;
+            (
+                status,
+                usage,
+                true,
+                matches!(outcome, WaitOutcome::TimedOut),
+            )
+        }
+    };
+    let duration = started.elapsed();
+
+    if terminated {
+        let outputs = async {
+            (&mut stdout_task).await.into_lua_err()??;
+            (&mut stderr_task).await.into_lua_err()??;
+            (&mut merged_task).await.into_lua_err()??;
+            Ok::<_, LuaError>(())
+        };
+        if timeout(TERMINATED_OUTPUT_TIMEOUT, outputs).await.is_err() {
+            stdout_task.abort();
+            stderr_task.abort();
+            merged_task.abort();
+        }
+    } else {
+        stdout_task.await.into_lua_err()??;
+        stderr_task.await.into_lua_err()??;
+        merged_task.await.into_lua_err()??;
+    }
 
-    let stdout_buffer = stdout_task.await.into_lua_err()??;
-    let stderr_buffer = stderr_task.await.into_lua_err()??;
+    let take_buffer = |buffer: SharedBuffer| {
+        std::mem::take(&mut *buffer.lock().expect("Failed to lock output buffer"))
+    };
 
     Ok(WaitForChildResult {
         status,
-        stdout: stdout_buffer,
-        stderr: stderr_buffer,
+        stdout: take_buffer(stdout_buffer),
+        stderr: take_buffer(stderr_buffer),
+        output: merged_buffer.map(take_buffer),
+        timed_out,
+        pid,
+        duration,
+        resource_usage,
     })
 }
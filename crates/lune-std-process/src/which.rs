@@ -0,0 +1,73 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+#[cfg(windows)]
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+
+/**
+    Finds the full path to an executable with the given name.
+
+    Names containing a path separator are resolved relative to the current
+    working directory, and all other names are searched for in `PATH`,
+    also trying all of the extensions in `PATHEXT` on Windows.
+*/
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    if name.is_empty() {
+        return None;
+    }
+
+    let path = Path::new(name);
+    if path.components().count() > 1 {
+        return find_with_extensions(path).and_then(|found| found.canonicalize().ok());
+    }
+
+    let dirs = env::var_os("PATH")?;
+    env::split_paths(&dirs)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find_map(|dir| find_with_extensions(&dir.join(name)))
+}
+
+#[cfg(unix)]
+fn find_with_extensions(path: &Path) -> Option<PathBuf> {
+    is_executable(path).then(|| path.to_path_buf())
+}
+
+#[cfg(windows)]
+fn find_with_extensions(path: &Path) -> Option<PathBuf> {
+    let pathext = env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.to_string());
+    let extensions = pathext
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .collect::<Vec<_>>();
+
+    // NOTE: Names that already have one of the executable
+    // extensions should be found as-is, without adding another
+    let has_extension = path.extension().is_some_and(|ext| {
+        let ext = format!(".{}", ext.to_string_lossy());
+        extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext))
+    });
+    if has_extension {
+        return is_executable(path).then(|| path.to_path_buf());
+    }
+
+    extensions.iter().find_map(|ext| {
+        let mut candidate = std::ffi::OsString::from(path.as_os_str());
+        candidate.push(ext);
+        let candidate = PathBuf::from(candidate);
+        is_executable(&candidate).then_some(candidate)
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.metadata().is_ok_and(|meta| meta.is_file())
+}
@@ -1,6 +1,7 @@
 #![allow(clippy::cargo_common_metadata)]
 
 use context::GlobalsContext;
+pub use library::cleanup_lune_standard_libraries as cleanup_libraries;
 pub use library::inject_lune_standard_libraries as inject_libraries;
 use mlua::prelude::*;
 
@@ -29,5 +30,10 @@ pub fn inject_globals(lua: &Lua, globals_ctx: &GlobalsContext) -> LuaResult<()>
             .set(global.name(), global.create(lua, globals_ctx)?)?;
     }
 
+    // NOTE: Replacing the current process skips dropping the Lua
+    // state, so standard libraries must be cleaned up before that
+    #[cfg(feature = "process")]
+    lua.set_app_data(lune_std_process::ProcessExecHook(cleanup_libraries));
+
     Ok(())
 }
@@ -8,6 +8,7 @@ pub enum LuneStandardLibrary {
     #[cfg(feature = "fs")]      Fs,
     #[cfg(feature = "luau")]    Luau,
     #[cfg(feature = "net")]     Net,
+    #[cfg(feature = "path")]    Path,
     #[cfg(feature = "task")]    Task,
     #[cfg(feature = "process")] Process,
     #[cfg(feature = "regex")]   Regex,
@@ -23,6 +24,7 @@ impl LuneStandardLibrary {
         #[cfg(feature = "fs")]      Self::Fs,
         #[cfg(feature = "luau")]    Self::Luau,
         #[cfg(feature = "net")]     Self::Net,
+        #[cfg(feature = "path")]    Self::Path,
         #[cfg(feature = "task")]    Self::Task,
         #[cfg(feature = "process")] Self::Process,
         #[cfg(feature = "regex")]   Self::Regex,
@@ -42,6 +44,7 @@ impl LuneStandardLibrary {
             #[cfg(feature = "fs")]      Fs      => "fs",
             #[cfg(feature = "luau")]    Luau    => "luau",
             #[cfg(feature = "net")]     Net     => "net",
+            #[cfg(feature = "path")]    Path    => "path",
             #[cfg(feature = "task")]    Task    => "task",
             #[cfg(feature = "process")] Process => "process",
             #[cfg(feature = "regex")]   Regex   => "regex",
@@ -62,6 +65,7 @@ impl LuneStandardLibrary {
             #[cfg(feature = "fs")]      Fs      => LuneModuleCreator::LuaTable(lune_std_fs::module),
             #[cfg(feature = "luau")]    Luau    => LuneModuleCreator::LuaTable(lune_std_luau::module),
             #[cfg(feature = "net")]     Net     => LuneModuleCreator::LuaTable(lune_std_net::module),
+            #[cfg(feature = "path")]    Path    => LuneModuleCreator::LuaTable(lune_std_path::module),
             #[cfg(feature = "task")]    Task    => LuneModuleCreator::LuaTable(lune_std_task::module),
             #[cfg(feature = "process")] Process => LuneModuleCreator::LuaTable(lune_std_process::module),
             #[cfg(feature = "regex")]   Regex   => LuneModuleCreator::LuaTable(lune_std_regex::module),
@@ -84,3 +88,14 @@ pub fn inject_lune_standard_libraries(
         Ok(())
     })
 }
+
+/**
+    Cleans up any resources held by standard libraries in the given Lua state,
+    such as temporary files, which should not outlive the Lua state itself.
+*/
+pub fn cleanup_lune_standard_libraries(lua: &Lua) {
+    #[cfg(feature = "fs")]
+    lune_std_fs::cleanup(lua);
+
+    let _ = lua;
+}
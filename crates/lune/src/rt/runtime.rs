@@ -177,3 +177,12 @@ impl Runtime {
         Ok(exit_code)
     }
 }
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // NOTE: Resources such as temporary files are normally cleaned up when
+        // garbage collected, but the Lua VM may be kept alive for longer than
+        // the runtime itself by background tasks, so we clean them up here too
+        lune_std::cleanup_libraries(self.inner.lua());
+    }
+}
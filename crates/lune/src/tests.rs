@@ -59,6 +59,7 @@ create_tests! {
     feature = "std-fs",
     feature = "std-luau",
     feature = "std-net",
+    feature = "std-path",
     feature = "std-process",
     feature = "std-regex",
     feature = "std-roblox",
@@ -108,10 +109,18 @@ create_tests! {
 #[cfg(feature = "std-fs")]
 create_tests! {
     fs_files: "fs/files",
+    fs_file: "fs/file",
+    fs_attributes: "fs/attributes",
     fs_copy: "fs/copy",
     fs_dirs: "fs/dirs",
+    fs_entries: "fs/entries",
+    fs_links: "fs/links",
+    fs_lock: "fs/lock",
     fs_metadata: "fs/metadata",
     fs_move: "fs/move",
+    fs_temp: "fs/temp",
+    fs_walk: "fs/walk",
+    fs_watch: "fs/watch",
 }
 
 #[cfg(feature = "std-luau")]
@@ -131,26 +140,57 @@ create_tests! {
     net_request_redirect: "net/request/redirect",
     net_url_encode: "net/url/encode",
     net_url_decode: "net/url/decode",
+    net_serve_http2: "net/serve/http2",
+    net_serve_metadata: "net/serve/metadata",
     net_serve_requests: "net/serve/requests",
+    net_serve_streaming: "net/serve/streaming",
+    net_serve_tls: "net/serve/tls",
+    net_serve_uploads: "net/serve/uploads",
     net_serve_websockets: "net/serve/websockets",
     net_socket_basic: "net/socket/basic",
     net_socket_wss: "net/socket/wss",
     net_socket_wss_rw: "net/socket/wss_rw",
 }
 
+#[cfg(feature = "std-path")]
+create_tests! {
+    path_absolute: "path/absolute",
+    path_general: "path/general",
+}
+
 #[cfg(feature = "std-process")]
 create_tests! {
     process_args: "process/args",
+    process_create_basic: "process/create/basic",
+    process_create_kill: "process/create/kill",
+    process_create_stdio: "process/create/stdio",
     process_cwd: "process/cwd",
     process_env: "process/env",
+    process_exec: "process/exec",
     process_exit: "process/exit",
+    process_info: "process/info",
+    process_pty_create: "process/pty/create",
+    process_pty_spawn: "process/pty/spawn",
+    process_signal_exit: "process/signal/exit",
+    process_signal_handlers: "process/signal/handlers",
     process_spawn_async: "process/spawn/async",
     process_spawn_basic: "process/spawn/basic",
+    process_spawn_callback: "process/spawn/callback",
+    process_spawn_cancel: "process/spawn/cancel",
     process_spawn_cwd: "process/spawn/cwd",
+    process_spawn_env: "process/spawn/env",
+    process_spawn_file: "process/spawn/file",
+    process_spawn_merged: "process/spawn/merged",
     process_spawn_no_panic: "process/spawn/no_panic",
+    process_spawn_pipeline: "process/spawn/pipeline",
     process_spawn_shell: "process/spawn/shell",
     process_spawn_stdin: "process/spawn/stdin",
+    process_spawn_status: "process/spawn/status",
     process_spawn_stdio: "process/spawn/stdio",
+    process_spawn_timeout: "process/spawn/timeout",
+    process_spawn_usage: "process/spawn/usage",
+    process_spawn_user: "process/spawn/user",
+    process_which: "process/which",
 }
 
 #[cfg(feature = "std-regex")]
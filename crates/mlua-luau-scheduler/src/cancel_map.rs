@@ -0,0 +1,81 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+// NOTE: This is the hash algorithm that mlua also uses, so we
+// are not adding any additional dependencies / bloat by using it.
+use rustc_hash::FxHashMap;
+
+use crate::thread_id::ThreadId;
+
+type CancelCallbacks = Vec<(usize, Box<dyn FnOnce()>)>;
+
+#[derive(Clone)]
+pub(crate) struct ThreadCancelMap {
+    next_key: Rc<Cell<usize>>,
+    callbacks: Rc<RefCell<FxHashMap<ThreadId, CancelCallbacks>>>,
+}
+
+impl ThreadCancelMap {
+    pub fn new() -> Self {
+        Self {
+            next_key: Rc::new(Cell::new(0)),
+            callbacks: Rc::new(RefCell::new(FxHashMap::default())),
+        }
+    }
+
+    pub fn insert(&self, id: ThreadId, callback: impl FnOnce() + 'static) -> ThreadCancelGuard {
+        let key = self.next_key.get();
+        self.next_key.set(key.wrapping_add(1));
+        self.callbacks
+            .borrow_mut()
+            .entry(id)
+            .or_default()
+            .push((key, Box::new(callback)));
+        ThreadCancelGuard {
+            map: self.clone(),
+            id,
+            key,
+        }
+    }
+
+    pub fn cancel(&self, id: ThreadId) {
+        // NOTE: Callbacks must be taken out before running them,
+        // since they may register or remove other callbacks
+        let callbacks = self.callbacks.borrow_mut().remove(&id);
+        for (_, callback) in callbacks.into_iter().flatten() {
+            callback();
+        }
+    }
+
+    fn remove(&self, id: ThreadId, key: usize) {
+        let mut callbacks = self.callbacks.borrow_mut();
+        if let Some(thread_callbacks) = callbacks.get_mut(&id) {
+            thread_callbacks.retain(|(k, _)| *k != key);
+            if thread_callbacks.is_empty() {
+                callbacks.remove(&id);
+            }
+        }
+    }
+}
+
+/**
+    A guard for a callback registered using [`LuaSchedulerExt::on_thread_cancel`].
+
+    The callback is unregistered without running when this guard is dropped.
+
+    [`LuaSchedulerExt::on_thread_cancel`]: crate::LuaSchedulerExt::on_thread_cancel
+*/
+#[must_use = "the callback is unregistered as soon as the guard is dropped"]
+pub struct ThreadCancelGuard {
+    map: ThreadCancelMap,
+    id: ThreadId,
+    key: usize,
+}
+
+impl Drop for ThreadCancelGuard {
+    fn drop(&mut self) {
+        self.map.remove(self.id, self.key);
+    }
+}
@@ -6,6 +6,7 @@ use std::process::ExitCode;
 use mlua::prelude::*;
 
 use crate::{
+    cancel_map::ThreadCancelMap,
     error_callback::ThreadErrorCallback,
     queue::{DeferredThreadQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
@@ -221,10 +222,18 @@ impl<'lua> Functions<'lua> {
         let cancel = lua.create_function(move |lua, thread: LuaThread| {
             let _span = tracing::trace_span!("Scheduler::fn_cancel").entered();
             let close: LuaFunction = lua.registry_value(&close_key)?;
+            let id = ThreadId::from(&thread);
             match close.call(thread) {
-                Err(LuaError::CoroutineInactive) | Ok(()) => Ok(()),
-                Err(e) => Err(e),
+                Err(LuaError::CoroutineInactive) | Ok(()) => {}
+                Err(e) => return Err(e),
             }
+            // NOTE: We must not hold on to app data while running
+            // callbacks, since they may want to access it themselves
+            let cancel_map = lua.app_data_ref::<ThreadCancelMap>().map(|m| m.clone());
+            if let Some(cancel_map) = cancel_map {
+                cancel_map.cancel(id);
+            }
+            Ok(())
         })?;
 
         let exit_env = lua.create_table_from(vec![
@@ -1,5 +1,6 @@
 #![allow(clippy::cargo_common_metadata)]
 
+mod cancel_map;
 mod error_callback;
 mod exit;
 mod functions;
@@ -11,6 +12,7 @@ mod thread_id;
 mod traits;
 mod util;
 
+pub use cancel_map::ThreadCancelGuard;
 pub use functions::Functions;
 pub use scheduler::Scheduler;
 pub use status::Status;
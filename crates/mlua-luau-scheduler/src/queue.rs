@@ -137,3 +137,20 @@ impl<'fut> FuturesQueue<'fut> {
         }
     }
 }
+
+/**
+    Alias for [`FuturesQueue`], providing a newtype to store in Lua app data.
+
+    Futures in this queue run in the background, and do not
+    prevent the [`Scheduler`] from completing when done.
+
+    [`Scheduler`]: crate::Scheduler
+*/
+#[derive(Debug, Clone, Deref, DerefMut)]
+pub(crate) struct BackgroundFuturesQueue<'fut>(FuturesQueue<'fut>);
+
+impl BackgroundFuturesQueue<'_> {
+    pub fn new() -> Self {
+        Self(FuturesQueue::new())
+    }
+}
@@ -15,9 +15,10 @@ use async_executor::{Executor, LocalExecutor};
 use tracing::{debug, instrument, trace, trace_span, Instrument};
 
 use crate::{
+    cancel_map::ThreadCancelMap,
     error_callback::ThreadErrorCallback,
     exit::Exit,
-    queue::{DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
+    queue::{BackgroundFuturesQueue, DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
     status::Status,
     thread_id::ThreadId,
@@ -70,6 +71,7 @@ impl<'lua> Scheduler<'lua> {
         let queue_defer = DeferredThreadQueue::new();
         let error_callback = ThreadErrorCallback::default();
         let result_map = ThreadResultMap::new();
+        let cancel_map = ThreadCancelMap::new();
         let exit = Exit::new();
 
         assert!(
@@ -88,6 +90,10 @@ impl<'lua> Scheduler<'lua> {
             lua.app_data_ref::<ThreadResultMap>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
         );
+        assert!(
+            lua.app_data_ref::<ThreadCancelMap>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
         assert!(
             lua.app_data_ref::<Exit>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
@@ -97,6 +103,7 @@ impl<'lua> Scheduler<'lua> {
         lua.set_app_data(queue_defer.clone());
         lua.set_app_data(error_callback.clone());
         lua.set_app_data(result_map.clone());
+        lua.set_app_data(cancel_map);
         lua.set_app_data(exit.clone());
 
         let status = Rc::new(Cell::new(Status::NotStarted));
@@ -288,6 +295,8 @@ impl<'lua> Scheduler<'lua> {
         let local_exec = LocalExecutor::new();
         let main_exec = Arc::new(Executor::new());
         let fut_queue = Rc::new(FuturesQueue::new());
+        let background_exec = LocalExecutor::new();
+        let background_queue = Rc::new(BackgroundFuturesQueue::new());
 
         /*
             Store the main executor and queue in Lua, so that they may be used with LuaSchedulerExt.
@@ -303,9 +312,17 @@ impl<'lua> Scheduler<'lua> {
             self.lua.app_data_ref::<WeakRc<FuturesQueue>>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
         );
+        assert!(
+            self.lua
+                .app_data_ref::<WeakRc<BackgroundFuturesQueue>>()
+                .is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
 
         self.lua.set_app_data(Arc::downgrade(&main_exec));
         self.lua.set_app_data(Rc::downgrade(&fut_queue.clone()));
+        self.lua
+            .set_app_data(Rc::downgrade(&background_queue.clone()));
 
         /*
             Manually tick the Lua executor, while running under the main executor.
@@ -316,6 +333,8 @@ impl<'lua> Scheduler<'lua> {
             3. A Lua thread is available to run on the deferred queue
             4. A new thread-local future is available to run on the local executor
             5. Task(s) scheduled on the Lua executor have made progress and should be polled again
+            6. A new background future is available to run on the background executor
+            7. Task(s) scheduled on the background executor have made progress and should be polled again
 
             This ordering is vital to ensure that we don't accidentally exit the main loop
             when there are new Lua threads to enqueue and potentially more work to be done.
@@ -379,12 +398,21 @@ impl<'lua> Scheduler<'lua> {
                     }
                 };
 
-                // 1 + 2 + 3 + 4 + 5
+                // 6 + 7
+                let fut_background_futs = background_queue.wait_for_item();
+                let fut_background_tick = async {
+                    background_exec.tick().await;
+                    while background_exec.try_tick() {}
+                };
+
+                // 1 + 2 + 3 + 4 + 5 + 6 + 7
                 fut_exit
                     .or(fut_spawn)
                     .or(fut_defer)
                     .or(fut_futs)
                     .or(fut_tick.instrument(span_tick.or_current()))
+                    .or(fut_background_futs)
+                    .or(fut_background_tick)
                     .await;
 
                 // Check if we should exit
@@ -418,6 +446,15 @@ impl<'lua> Scheduler<'lua> {
                         num_futures += 1;
                     }
                 }
+                {
+                    let _span = trace_span!("Scheduler::drain_background_futures").entered();
+                    for fut in background_queue.drain_items() {
+                        background_exec.spawn(fut).detach();
+                    }
+                }
+
+                // NOTE: Background futures are intentionally not checked here,
+                // they should never keep the scheduler running by themselves
 
                 // Empty executor = we didn't spawn any new Lua tasks
                 // above, and there are no remaining tasks to run later
@@ -449,6 +486,9 @@ impl<'lua> Scheduler<'lua> {
         self.lua
             .remove_app_data::<WeakRc<FuturesQueue>>()
             .expect(ERR_METADATA_REMOVED);
+        self.lua
+            .remove_app_data::<WeakRc<BackgroundFuturesQueue>>()
+            .expect(ERR_METADATA_REMOVED);
     }
 }
 
@@ -461,6 +501,7 @@ impl Drop for Scheduler<'_> {
             self.lua.remove_app_data::<DeferredThreadQueue>();
             self.lua.remove_app_data::<ThreadErrorCallback>();
             self.lua.remove_app_data::<ThreadResultMap>();
+            self.lua.remove_app_data::<ThreadCancelMap>();
             self.lua.remove_app_data::<Exit>();
         } else {
             // In any other case we panic if metadata was removed incorrectly
@@ -476,6 +517,9 @@ impl Drop for Scheduler<'_> {
             self.lua
                 .remove_app_data::<ThreadResultMap>()
                 .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<ThreadCancelMap>()
+                .expect(ERR_METADATA_REMOVED);
             self.lua
                 .remove_app_data::<Exit>()
                 .expect(ERR_METADATA_REMOVED);
@@ -10,8 +10,10 @@ use mlua::prelude::*;
 use tracing::trace;
 
 use crate::{
+    cancel_map::{ThreadCancelGuard, ThreadCancelMap},
+    error_callback::ThreadErrorCallback,
     exit::Exit,
-    queue::{DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
+    queue::{BackgroundFuturesQueue, DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
     scheduler::Scheduler,
     thread_id::ThreadId,
@@ -71,6 +73,7 @@ where
     - Setting the exit code and forcibly stopping the scheduler
     - Pushing (spawning) and deferring (pushing to the back) lua threads
     - Tracking and getting the result of lua threads
+    - Reporting errors using the error callback of the scheduler
 */
 pub trait LuaSchedulerExt<'lua> {
     /**
@@ -142,6 +145,39 @@ pub trait LuaSchedulerExt<'lua> {
         Panics if called outside of a running [`Scheduler`].
     */
     fn wait_for_thread(&'lua self, id: ThreadId) -> impl Future<Output = ()>;
+
+    /**
+        Registers a callback to run when the given thread is cancelled using the `cancel` function.
+
+        This is useful for cleaning up resources that are owned by an async function, since
+        the future of an async function is not dropped until its thread is garbage collected.
+
+        The callback is unregistered without running when the returned guard is dropped.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn on_thread_cancel(
+        &'lua self,
+        id: ThreadId,
+        callback: impl FnOnce() + 'static,
+    ) -> ThreadCancelGuard;
+
+    /**
+        Reports an error using the error callback of the current scheduler,
+        the same way as errors thrown by lua threads are reported.
+
+        This is useful for errors that happen in background tasks,
+        which have no lua thread that they could be propagated to.
+
+        See [`Scheduler::set_error_callback`] for more information.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn report_error(&self, error: &LuaError);
 }
 
 /**
@@ -150,6 +186,7 @@ pub trait LuaSchedulerExt<'lua> {
     Provides extra methods on the [`Lua`] struct for:
 
     - Spawning thread-local (`!Send`) futures on the current executor
+    - Spawning thread-local (`!Send`) background futures on the current executor
     - Spawning background (`Send`) futures on the current executor
     - Spawning blocking tasks on a separate thread pool
 */
@@ -238,6 +275,24 @@ pub trait LuaSpawnExt<'lua> {
     where
         F: Future<Output = ()> + 'static;
 
+    /**
+        Spawns the given thread-local future on the current executor, in the background.
+
+        Unlike [`LuaSpawnExt::spawn_local`], this future will not prevent the [`Scheduler`]
+        it was spawned on from completing, and will be dropped if it has not yet completed
+        by the time all other Lua threads and futures are done.
+
+        This is useful for futures that listen for some external event for as long
+        as the scheduler is running, such as signals sent to the current process.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn spawn_local_background<F>(&self, fut: F)
+    where
+        F: Future<Output = ()> + 'static;
+
     /**
         Spawns the given blocking function and returns its [`Task`].
 
@@ -332,6 +387,24 @@ impl<'lua> LuaSchedulerExt<'lua> for Lua {
             .expect("lua threads results can only be retrieved from within an active scheduler");
         async move { map.listen(id).await }
     }
+
+    fn on_thread_cancel(
+        &'lua self,
+        id: ThreadId,
+        callback: impl FnOnce() + 'static,
+    ) -> ThreadCancelGuard {
+        let map = self
+            .app_data_ref::<ThreadCancelMap>()
+            .expect("cancel callbacks can only be registered from within an active scheduler");
+        map.insert(id, callback)
+    }
+
+    fn report_error(&self, error: &LuaError) {
+        let callback = self
+            .app_data_ref::<ThreadErrorCallback>()
+            .expect("errors can only be reported from within an active scheduler");
+        callback.call(error);
+    }
 }
 
 impl<'lua> LuaSpawnExt<'lua> for Lua {
@@ -362,6 +435,19 @@ impl<'lua> LuaSpawnExt<'lua> for Lua {
         queue.push_item(fut);
     }
 
+    fn spawn_local_background<F>(&self, fut: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        let queue = self
+            .app_data_ref::<WeakRc<BackgroundFuturesQueue>>()
+            .expect("tasks can only be spawned within an active scheduler")
+            .upgrade()
+            .expect("executor was dropped");
+        trace!("spawning local background task on executor");
+        queue.push_item(fut);
+    }
+
     fn spawn_blocking<F, T>(&self, f: F) -> Task<T>
     where
         F: FnOnce() -> T + Send + 'static,